@@ -4,7 +4,7 @@ use serde_json::json;
 #[mcp_component]
 fn handle_request(request: Request) -> Response {
     match request {
-        Request::ToolsList => {
+        Request::ToolsList(_) => {
             // TODO: Define your tools here
             Response::ToolsList(vec![
                 Tool {
@@ -20,8 +20,11 @@ fn handle_request(request: Request) -> Response {
                         },
                         "required": ["message"]
                     }).to_string(),
+                    output_schema: None,
+                    title: None,
+                    annotations: None,
                 }
-            ])
+            ].into())
         }
         
         Request::ToolsCall(params) => {
@@ -47,16 +50,8 @@ fn handle_request(request: Request) -> Response {
             }
         }
         
-        Request::ResourcesList => {
-            // TODO: Add resources if needed
-            Response::ResourcesList(vec![])
-        }
-        
-        Request::PromptsList => {
-            // TODO: Add prompts if needed
-            Response::PromptsList(vec![])
-        }
-        
+        // No resources or prompts: answering their list requests with
+        // "method not found" keeps them out of the advertised capabilities
         Request::Ping => Response::Pong,
         
         _ => Response::Error(Error {
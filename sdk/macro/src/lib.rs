@@ -3,12 +3,40 @@ use quote::quote;
 
 const WIT_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../spin-mcp.wit");
 
+/// Turns a `fn(Request) -> Response` into an MCP component.
+///
+/// An optional `init` function is called once when the trigger starts, before
+/// any requests are served. Returning an error stops the trigger, which makes
+/// it a good place to validate configuration:
+///
+/// ```ignore
+/// #[mcp_component(init = check_config)]
+/// fn handle_request(request: Request) -> Response { ... }
+///
+/// fn check_config() -> Result<(), String> { ... }
+/// ```
 #[proc_macro_attribute]
-pub fn mcp_component(_attr: TokenStream, item: TokenStream) -> TokenStream {
+pub fn mcp_component(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut init: Option<syn::Path> = None;
+    let attr_parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("init") {
+            init = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("unsupported mcp_component argument, expected `init`"))
+        }
+    });
+    syn::parse_macro_input!(attr with attr_parser);
+
     let func = syn::parse_macro_input!(item as syn::ItemFn);
     let func_name = &func.sig.ident;
     let preamble = preamble();
 
+    let initialize = match init.map(resolve_from_parent) {
+        Some(init) => quote!(#init()),
+        None => quote!(::std::result::Result::Ok(())),
+    };
+
     quote!(
         #func
         mod __spin_mcp {
@@ -21,13 +49,31 @@ pub fn mcp_component(_attr: TokenStream, item: TokenStream) -> TokenStream {
                 }
                 
                 fn initialize() -> ::std::result::Result<(), ::std::string::String> {
-                    ::std::result::Result::Ok(())
+                    #initialize
                 }
             }
         }
     ).into()
 }
 
+/// Rewrite a path written in the annotated function's module so it resolves
+/// from the generated module inside it. Absolute and `crate::` paths are
+/// kept as they are.
+fn resolve_from_parent(mut path: syn::Path) -> syn::Path {
+    if path.leading_colon.is_some() {
+        return path;
+    }
+    let first = &mut path.segments[0].ident;
+    if first == "crate" {
+        return path;
+    }
+    if first == "self" {
+        *first = syn::Ident::new("super", first.span());
+        return path;
+    }
+    syn::parse_quote!(super::#path)
+}
+
 fn preamble() -> proc_macro2::TokenStream {
     let world = "spin-mcp";
     quote! {
@@ -40,9 +86,15 @@ fn preamble() -> proc_macro2::TokenStream {
                 world: Mcp
             },
             with: {
-                "spin:mcp-trigger/mcp-types": ::spin_mcp_sdk::wit::spin::mcp_trigger::mcp_types,
+                "spin:mcp-trigger/mcp-types@0.2.0": ::spin_mcp_sdk::wit::spin::mcp_trigger::mcp_types,
+                "spin:mcp-trigger/notifications@0.2.0": ::spin_mcp_sdk::wit::spin::mcp_trigger::notifications,
+                "spin:mcp-trigger/logging@0.2.0": ::spin_mcp_sdk::wit::spin::mcp_trigger::logging,
+                "spin:mcp-trigger/progress@0.2.0": ::spin_mcp_sdk::wit::spin::mcp_trigger::progress,
+                "spin:mcp-trigger/sampling@0.2.0": ::spin_mcp_sdk::wit::spin::mcp_trigger::sampling,
+                "spin:mcp-trigger/elicitation@0.2.0": ::spin_mcp_sdk::wit::spin::mcp_trigger::elicitation,
+                "spin:mcp-trigger/roots@0.2.0": ::spin_mcp_sdk::wit::spin::mcp_trigger::roots,
             }
         });
         pub struct Mcp;
     }
-}
\ No newline at end of file
+}
@@ -13,8 +13,55 @@ pub mod wit {
 #[doc(hidden)]
 pub use wit_bindgen;
 
+/// Notifications to send to connected clients
+pub use wit::spin::mcp_trigger::notifications;
+
+/// Log records to send to the client of the current request
+pub use wit::spin::mcp_trigger::logging;
+
+/// Progress reports for long-running tool calls
+pub use wit::spin::mcp_trigger::progress;
+
+/// Requests to the language model of the client
+pub use wit::spin::mcp_trigger::sampling;
+
+/// Questions to the user of the client
+pub use wit::spin::mcp_trigger::elicitation;
+
+/// Directories and files the user opened in the client
+pub use wit::spin::mcp_trigger::roots;
+
 #[doc(inline)]
 pub use wit::spin::mcp_trigger::mcp_types::{
-    Error, Request, Response, Tool, ToolResult, Prompt, PromptArgument, 
-    PromptMessage, ResourceInfo, ResourceContents,
-};
\ No newline at end of file
+    Error, Request, Response, Tool, ToolAnnotations, ToolResult, Prompt, PromptArgument, 
+    PromptMessage, ResourceInfo, ResourceContents, Content, MediaContent,
+    ResourceTemplate, ListParams, ToolsPage, ResourcesPage, ResourceTemplatesPage, PromptsPage,
+};
+
+/// A single page holding every tool
+impl From<Vec<Tool>> for ToolsPage {
+    fn from(tools: Vec<Tool>) -> Self {
+        Self { tools, next_cursor: None }
+    }
+}
+
+/// A single page holding every resource
+impl From<Vec<ResourceInfo>> for ResourcesPage {
+    fn from(resources: Vec<ResourceInfo>) -> Self {
+        Self { resources, next_cursor: None }
+    }
+}
+
+/// A single page holding every resource template
+impl From<Vec<ResourceTemplate>> for ResourceTemplatesPage {
+    fn from(resource_templates: Vec<ResourceTemplate>) -> Self {
+        Self { resource_templates, next_cursor: None }
+    }
+}
+
+/// A single page holding every prompt
+impl From<Vec<Prompt>> for PromptsPage {
+    fn from(prompts: Vec<Prompt>) -> Self {
+        Self { prompts, next_cursor: None }
+    }
+}
@@ -0,0 +1,165 @@
+//! Opt-in caching of `tools/list`, `resources/list` and `prompts/list`
+//! results, so clients re-listing on every reconnect don't each cost a
+//! component instantiation.
+
+use crate::mcp;
+use crate::route::Kind;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long a route's list results are cached
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "RawListCacheMode")]
+pub enum ListCacheMode {
+    /// Cache for a fixed time (`list_cache = 300`, in seconds)
+    Ttl(Duration),
+    /// Cache until the trigger restarts or the list changes
+    /// (`list_cache = "until-restart"`)
+    UntilRestart,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawListCacheMode {
+    Seconds(u64),
+    Name(String),
+}
+
+impl TryFrom<RawListCacheMode> for ListCacheMode {
+    type Error = String;
+
+    fn try_from(raw: RawListCacheMode) -> Result<Self, Self::Error> {
+        match raw {
+            RawListCacheMode::Seconds(secs) => Ok(Self::Ttl(Duration::from_secs(secs))),
+            RawListCacheMode::Name(name) if name == "until-restart" => Ok(Self::UntilRestart),
+            RawListCacheMode::Name(name) => Err(format!(
+                "invalid list_cache value {name:?}, expected a number of seconds or \"until-restart\""
+            )),
+        }
+    }
+}
+
+/// Cached list results by route and kind
+#[derive(Default)]
+pub(crate) struct ListCache {
+    entries: Mutex<HashMap<(String, Kind), CacheEntry>>,
+    /// Complete merged lists of routes the trigger paginates, which later
+    /// pages are served from
+    merged: Mutex<HashMap<(String, Kind), CacheEntry>>,
+}
+
+struct CacheEntry {
+    response: mcp::Response,
+    expires: Option<Instant>,
+}
+
+impl ListCache {
+    /// A cached list result for a route, if it hasn't expired
+    pub(crate) fn get(&self, route: &str, kind: Kind) -> Option<mcp::Response> {
+        lookup(&self.entries, route, kind)
+    }
+
+    pub(crate) fn insert(&self, route: &str, kind: Kind, mode: ListCacheMode, response: mcp::Response) {
+        store(&self.entries, route, kind, mode, response);
+    }
+
+    /// The complete merged list of a paginated route, if it hasn't expired
+    pub(crate) fn get_merged(&self, route: &str, kind: Kind) -> Option<mcp::Response> {
+        lookup(&self.merged, route, kind)
+    }
+
+    pub(crate) fn insert_merged(&self, route: &str, kind: Kind, mode: ListCacheMode, response: mcp::Response) {
+        store(&self.merged, route, kind, mode, response);
+    }
+
+    /// Forget a route's cached list, e.g. when its components report that
+    /// the list has changed
+    pub(crate) fn invalidate(&self, route: &str, kind: Kind) {
+        let key = (route.to_string(), kind);
+        self.entries.lock().unwrap().remove(&key);
+        self.merged.lock().unwrap().remove(&key);
+    }
+}
+
+fn lookup(entries: &Mutex<HashMap<(String, Kind), CacheEntry>>, route: &str, kind: Kind) -> Option<mcp::Response> {
+    let mut entries = entries.lock().unwrap();
+    let key = (route.to_string(), kind);
+    let entry = entries.get(&key)?;
+    if entry.expires.is_some_and(|expires| expires <= Instant::now()) {
+        entries.remove(&key);
+        return None;
+    }
+    Some(entry.response.clone())
+}
+
+fn store(
+    entries: &Mutex<HashMap<(String, Kind), CacheEntry>>,
+    route: &str,
+    kind: Kind,
+    mode: ListCacheMode,
+    response: mcp::Response,
+) {
+    let expires = match mode {
+        ListCacheMode::Ttl(ttl) => Some(Instant::now() + ttl),
+        ListCacheMode::UntilRestart => None,
+    };
+    entries
+        .lock()
+        .unwrap()
+        .insert((route.to_string(), kind), CacheEntry { response, expires });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cached(cache: &ListCache, kind: Kind) -> bool {
+        cache.get("/mcp", kind).is_some()
+    }
+
+    #[test]
+    fn list_cache_mode_parses_seconds_and_until_restart() {
+        let mode = |value: serde_json::Value| serde_json::from_value::<ListCacheMode>(value);
+        assert_eq!(mode(serde_json::json!(300)).unwrap(), ListCacheMode::Ttl(Duration::from_secs(300)));
+        assert_eq!(mode(serde_json::json!("until-restart")).unwrap(), ListCacheMode::UntilRestart);
+        assert!(mode(serde_json::json!("forever")).is_err());
+    }
+
+    #[test]
+    fn entries_expire_after_their_ttl() {
+        let cache = ListCache::default();
+        cache.insert("/mcp", Kind::Tool, ListCacheMode::Ttl(Duration::ZERO), mcp::Response::Pong);
+        cache.insert("/mcp", Kind::Prompt, ListCacheMode::Ttl(Duration::from_secs(60)), mcp::Response::Pong);
+
+        assert!(!cached(&cache, Kind::Tool));
+        assert!(cached(&cache, Kind::Prompt));
+    }
+
+    #[test]
+    fn entries_are_kept_by_route_and_kind() {
+        let cache = ListCache::default();
+        cache.insert("/mcp", Kind::Tool, ListCacheMode::UntilRestart, mcp::Response::Pong);
+
+        assert!(cached(&cache, Kind::Tool));
+        assert!(!cached(&cache, Kind::Prompt));
+        assert!(cache.get("/other", Kind::Tool).is_none());
+    }
+
+    #[test]
+    fn invalidation_drops_cached_and_merged_lists() {
+        let cache = ListCache::default();
+        for kind in [Kind::Tool, Kind::Prompt] {
+            cache.insert("/mcp", kind, ListCacheMode::UntilRestart, mcp::Response::Pong);
+            cache.insert_merged("/mcp", kind, ListCacheMode::UntilRestart, mcp::Response::Pong);
+        }
+
+        cache.invalidate("/mcp", Kind::Tool);
+
+        assert!(!cached(&cache, Kind::Tool));
+        assert!(cache.get_merged("/mcp", Kind::Tool).is_none());
+        assert!(cached(&cache, Kind::Prompt));
+        assert!(cache.get_merged("/mcp", Kind::Prompt).is_some());
+    }
+}
@@ -0,0 +1,124 @@
+//! Server capabilities advertised in the `initialize` result, derived from
+//! what the components behind each route actually provide.
+
+use crate::host::RequestContext;
+use crate::route::McpRoute;
+use crate::{mcp, McpServer};
+use anyhow::Result;
+use spin_factors::RuntimeFactors;
+use std::collections::HashMap;
+use tracing::{info, warn};
+
+/// Which MCP features a route offers
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Capabilities {
+    tools: bool,
+    resources: bool,
+    prompts: bool,
+}
+
+impl Capabilities {
+    /// Assumed when a component can't be probed
+    const ALL: Self = Self {
+        tools: true,
+        resources: true,
+        prompts: true,
+    };
+
+    fn union(self, other: Self) -> Self {
+        Self {
+            tools: self.tools || other.tools,
+            resources: self.resources || other.resources,
+            prompts: self.prompts || other.prompts,
+        }
+    }
+
+    /// The `capabilities` object of the `initialize` result
+    pub(crate) fn to_json(self) -> serde_json::Value {
+        // Any component can report list changes and resource updates
+        // through the host, and the trigger tracks subscriptions for them
+        let mut capabilities = serde_json::json!({});
+        if self.tools {
+            capabilities["tools"] = serde_json::json!({ "listChanged": true });
+        }
+        if self.resources {
+            capabilities["resources"] = serde_json::json!({ "subscribe": true, "listChanged": true });
+        }
+        if self.prompts {
+            capabilities["prompts"] = serde_json::json!({ "listChanged": true });
+        }
+        // Components can always log through the host
+        capabilities["logging"] = serde_json::json!({});
+        capabilities
+    }
+}
+
+impl<F: RuntimeFactors> McpServer<F> {
+    /// Work out the capabilities of each route by listing the tools,
+    /// resources and prompts of its components
+    pub(crate) async fn probe_capabilities<'a>(&self, routes: impl IntoIterator<Item = &'a McpRoute>) {
+        let mut by_component: HashMap<&str, Capabilities> = HashMap::new();
+        let mut by_route = HashMap::new();
+
+        for route in routes {
+            let mut capabilities = Capabilities::default();
+            for component in &route.components {
+                let component_capabilities = match by_component.get(component.id.as_str()) {
+                    Some(capabilities) => *capabilities,
+                    None => {
+                        let probed = self.probe_component(&component.id).await.unwrap_or_else(|err| {
+                            warn!("Failed to probe MCP component {}, advertising all capabilities: {err:?}", component.id);
+                            Capabilities::ALL
+                        });
+                        info!("MCP component {} provides {:?}", component.id, probed);
+                        by_component.insert(&component.id, probed);
+                        probed
+                    }
+                };
+                capabilities = capabilities.union(component_capabilities);
+            }
+            by_route.insert(route.path.clone(), capabilities);
+        }
+
+        self.capabilities.lock().unwrap().extend(by_route);
+    }
+
+    /// The capabilities advertised for a route
+    pub(crate) fn route_capabilities(&self, route: &McpRoute) -> Capabilities {
+        self.capabilities
+            .lock()
+            .unwrap()
+            .get(&route.path)
+            .copied()
+            .unwrap_or(Capabilities::ALL)
+    }
+
+    /// A component provides a feature if it answers the feature's list
+    /// request, even with an empty page: entries can appear at runtime, and
+    /// the component reports them with a `list_changed` notification.
+    /// Components that don't implement a feature answer with an error.
+    async fn probe_component(&self, component_id: &str) -> Result<Capabilities> {
+        // Probes don't belong to any client request
+        let context = RequestContext::default();
+        let first_page = || mcp::ListParams { cursor: None };
+        let tools = self
+            .call_component(component_id, &mcp::Request::ToolsList(first_page()), &context)
+            .await?;
+        let resources = self
+            .call_component(component_id, &mcp::Request::ResourcesList(first_page()), &context)
+            .await?;
+        let templates = self
+            .call_component(component_id, &mcp::Request::ResourcesTemplatesList(first_page()), &context)
+            .await?;
+        let prompts = self
+            .call_component(component_id, &mcp::Request::PromptsList(first_page()), &context)
+            .await?;
+
+        Ok(Capabilities {
+            tools: matches!(tools, mcp::Response::ToolsList(_)),
+            resources: matches!(resources, mcp::Response::ResourcesList(_))
+                || matches!(templates, mcp::Response::ResourcesTemplatesList(_)),
+            prompts: matches!(prompts, mcp::Response::PromptsList(_)),
+        })
+    }
+}
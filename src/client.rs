@@ -0,0 +1,462 @@
+//! Requests the trigger sends to clients on behalf of components, such as
+//! sampling from the client's language model or asking its user for input.
+
+use crate::host::RequestContext;
+use crate::notify::notification;
+use crate::protocol::ProtocolVersion;
+use crate::session::Session;
+use crate::spin::mcp_trigger::{elicitation, roots, sampling};
+use crate::{content_json, mcp, INTERNAL_ERROR, INVALID_PARAMS, INVALID_REQUEST};
+use base64::Engine;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// How long to wait for the client to answer a request. Clients often ask
+/// the user first, so this is generous.
+const CLIENT_REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+
+impl RequestContext {
+    /// Send a request to the client of the current request and wait for its
+    /// reply. Fails without sending anything unless the client declared the
+    /// given capability during `initialize`.
+    pub(crate) async fn request_client(
+        &self,
+        capability: &str,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value, mcp::Error> {
+        let Some(session) = &self.session else {
+            return Err(error(INVALID_REQUEST, format!("{method} can only be sent within a session")));
+        };
+        if !session.client_supports(capability) {
+            return Err(error(INVALID_REQUEST, format!("Client does not support {capability}")));
+        }
+
+        let (id, reply) = session.start_client_request();
+        // Forget the request however this returns, including when the
+        // component call is cancelled while waiting
+        let pending = PendingRequest { session, id };
+        let message = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": pending.id,
+            "method": method,
+            "params": params,
+        });
+        if !self.send(message) {
+            return Err(error(INTERNAL_ERROR, format!("No open stream to send {method} to the client")));
+        }
+        debug!("Sent {} request {} to MCP session {}", method, pending.id, session.id());
+
+        match tokio::time::timeout(CLIENT_REQUEST_TIMEOUT, reply).await {
+            Ok(Ok(reply)) => reply,
+            // The sender is dropped when the session ends
+            Ok(Err(_)) => Err(error(INTERNAL_ERROR, format!("Session ended before the client answered {method}"))),
+            Err(_) => {
+                warn!("MCP session {} did not answer {} request {} in time", session.id(), method, pending.id);
+                let params = serde_json::json!({ "requestId": pending.id, "reason": "Timed out" });
+                self.send(notification("notifications/cancelled", params));
+                Err(error(INTERNAL_ERROR, format!("Client did not answer {method} in time")))
+            }
+        }
+    }
+
+    /// The result of `roots/list` for the client of the current request,
+    /// asking the client only when its session has no cached copy
+    pub(crate) async fn list_roots(&self) -> Result<serde_json::Value, mcp::Error> {
+        let generation = match self.session.as_ref().map(|session| session.cached_roots()) {
+            Some(Ok(roots)) => return Ok(roots),
+            Some(Err(generation)) => generation,
+            None => 0,
+        };
+        let roots = self.request_client("roots", "roots/list", serde_json::json!({})).await?;
+        if let Some(session) = &self.session {
+            session.cache_roots(generation, roots.clone());
+        }
+        Ok(roots)
+    }
+}
+
+/// A request to the client that is waiting for a reply
+struct PendingRequest<'a> {
+    session: &'a Session,
+    id: serde_json::Value,
+}
+
+impl Drop for PendingRequest<'_> {
+    fn drop(&mut self) {
+        // A no-op once the reply has been delivered
+        self.session.abandon_client_request(&self.id);
+    }
+}
+
+/// Map `sampling/createMessage` params to MCP JSON
+pub(crate) fn create_message_params_json(
+    params: sampling::CreateMessageParams,
+    version: ProtocolVersion,
+) -> Result<serde_json::Value, mcp::Error> {
+    let messages = params
+        .messages
+        .into_iter()
+        .map(|message| {
+            if matches!(message.content, mcp::Content::EmbeddedResource(_) | mcp::Content::ResourceLink(_)) {
+                return Err(error(INVALID_PARAMS, "Sampling messages can only contain text, image or audio"));
+            }
+            Ok(serde_json::json!({
+                "role": message.role,
+                "content": content_json(message.content, version),
+            }))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut json = serde_json::json!({
+        "messages": messages,
+        "maxTokens": params.max_tokens,
+    });
+    if let Some(preferences) = params.model_preferences {
+        let hints: Vec<_> = preferences
+            .hints
+            .into_iter()
+            .map(|name| serde_json::json!({ "name": name }))
+            .collect();
+        let mut model_preferences = serde_json::json!({ "hints": hints });
+        let priorities = [
+            ("costPriority", preferences.cost_priority),
+            ("speedPriority", preferences.speed_priority),
+            ("intelligencePriority", preferences.intelligence_priority),
+        ];
+        for (key, priority) in priorities {
+            if let Some(priority) = priority {
+                model_preferences[key] = serde_json::json!(priority);
+            }
+        }
+        json["modelPreferences"] = model_preferences;
+    }
+    if let Some(system_prompt) = params.system_prompt {
+        json["systemPrompt"] = serde_json::json!(system_prompt);
+    }
+    if let Some(temperature) = params.temperature {
+        json["temperature"] = serde_json::json!(temperature);
+    }
+    if !params.stop_sequences.is_empty() {
+        json["stopSequences"] = serde_json::json!(params.stop_sequences);
+    }
+    Ok(json)
+}
+
+/// Map the client's `sampling/createMessage` result to WIT
+pub(crate) fn create_message_result(result: serde_json::Value) -> Result<sampling::CreateMessageResult, mcp::Error> {
+    let field = |key: &str| result.get(key).and_then(serde_json::Value::as_str).map(str::to_string);
+    let (Some(role), Some(model), Some(content)) = (field("role"), field("model"), result.get("content")) else {
+        return Err(invalid_result("missing role, model or content"));
+    };
+    Ok(sampling::CreateMessageResult {
+        role,
+        content: parse_content(content)?,
+        model,
+        stop_reason: field("stopReason"),
+    })
+}
+
+/// Map `elicitation/create` params to MCP JSON
+pub(crate) fn elicit_params_json(message: String, requested_schema: &str) -> Result<serde_json::Value, mcp::Error> {
+    let requested_schema: serde_json::Value = serde_json::from_str(requested_schema)
+        .map_err(|err| error(INVALID_PARAMS, format!("Requested schema is not valid JSON: {err}")))?;
+    Ok(serde_json::json!({
+        "message": message,
+        "requestedSchema": requested_schema,
+    }))
+}
+
+/// Map the client's `elicitation/create` result to WIT
+pub(crate) fn elicit_result(result: serde_json::Value) -> Result<elicitation::ElicitResult, mcp::Error> {
+    match result.get("action").and_then(serde_json::Value::as_str) {
+        Some("accept") => {
+            let content = result.get("content").cloned().unwrap_or_else(|| serde_json::json!({}));
+            Ok(elicitation::ElicitResult::Accept(content.to_string()))
+        }
+        Some("decline") => Ok(elicitation::ElicitResult::Decline),
+        Some("cancel") => Ok(elicitation::ElicitResult::Cancel),
+        other => Err(invalid_result(&format!("unknown elicitation action {other:?}"))),
+    }
+}
+
+/// Map the client's `roots/list` result to WIT
+pub(crate) fn roots_result(result: serde_json::Value) -> Result<Vec<roots::Root>, mcp::Error> {
+    let Some(entries) = result.get("roots").and_then(serde_json::Value::as_array) else {
+        return Err(invalid_result("missing roots"));
+    };
+    entries
+        .iter()
+        .map(|root| {
+            let uri = root
+                .get("uri")
+                .and_then(serde_json::Value::as_str)
+                .ok_or_else(|| invalid_result("root without uri"))?;
+            Ok(roots::Root {
+                uri: uri.to_string(),
+                name: root.get("name").and_then(serde_json::Value::as_str).map(str::to_string),
+            })
+        })
+        .collect()
+}
+
+/// Parse a text, image or audio content part sent by the client
+fn parse_content(content: &serde_json::Value) -> Result<mcp::Content, mcp::Error> {
+    let field = |key: &str| content.get(key).and_then(serde_json::Value::as_str);
+    let media = || -> Result<mcp::MediaContent, mcp::Error> {
+        let (Some(data), Some(mime_type)) = (field("data"), field("mimeType")) else {
+            return Err(invalid_result("media content without data or mimeType"));
+        };
+        let data = base64::engine::general_purpose::STANDARD
+            .decode(data)
+            .map_err(|err| invalid_result(&format!("invalid base64 data: {err}")))?;
+        Ok(mcp::MediaContent {
+            data,
+            mime_type: mime_type.to_string(),
+        })
+    };
+    match field("type") {
+        Some("text") => field("text")
+            .map(|text| mcp::Content::Text(text.to_string()))
+            .ok_or_else(|| invalid_result("text content without text")),
+        Some("image") => Ok(mcp::Content::Image(media()?)),
+        Some("audio") => Ok(mcp::Content::Audio(media()?)),
+        other => Err(invalid_result(&format!("unsupported content type {other:?}"))),
+    }
+}
+
+fn invalid_result(reason: &str) -> mcp::Error {
+    error(INTERNAL_ERROR, format!("Invalid reply from client: {reason}"))
+}
+
+fn error(code: i32, message: impl Into<String>) -> mcp::Error {
+    mcp::Error {
+        code,
+        message: message.into(),
+        data: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sse::{self, MessageReceiver};
+    use futures::{FutureExt, StreamExt};
+    use std::sync::Arc;
+
+    /// A session whose client declared the given capability, and the
+    /// stream it receives requests on
+    fn session_supporting(capability: &str) -> (Arc<Session>, MessageReceiver) {
+        let session = Arc::new(Session::new("/mcp"));
+        session.set_initialized(ProtocolVersion::LATEST, serde_json::json!({ capability: {} }));
+        let (sender, receiver) = sse::channel();
+        session.add_stream(sender);
+        (session, receiver)
+    }
+
+    fn context(session: &Arc<Session>) -> RequestContext {
+        RequestContext::new(Some(session), None, ProtocolVersion::LATEST)
+    }
+
+    #[tokio::test]
+    async fn client_replies_are_delivered_to_the_request() {
+        let (session, mut receiver) = session_supporting("sampling");
+        let context = context(&session);
+
+        let request = context.request_client("sampling", "sampling/createMessage", serde_json::json!({}));
+        let client = async {
+            let message = receiver.next().await.unwrap();
+            assert_eq!(message["method"], "sampling/createMessage");
+            let reply = Ok(serde_json::json!({ "model": "test" }));
+            assert!(session.complete_client_request(&message["id"], reply));
+        };
+        let (reply, ()) = futures::join!(request, client);
+
+        assert_eq!(reply.unwrap(), serde_json::json!({ "model": "test" }));
+    }
+
+    #[tokio::test]
+    async fn requests_need_a_session_and_the_client_capability() {
+        let (session, mut receiver) = session_supporting("roots");
+
+        let err = context(&session)
+            .request_client("sampling", "sampling/createMessage", serde_json::json!({}))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, INVALID_REQUEST);
+        assert!(receiver.next().now_or_never().is_none());
+
+        let err = RequestContext::default()
+            .request_client("roots", "roots/list", serde_json::json!({}))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, INVALID_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn abandoned_requests_are_forgotten() {
+        let (session, mut receiver) = session_supporting("roots");
+        let context = context(&session);
+
+        let mut request = Box::pin(context.request_client("roots", "roots/list", serde_json::json!({})));
+        assert!(futures::poll!(request.as_mut()).is_pending());
+        let message = receiver.next().await.unwrap();
+        drop(request);
+
+        assert!(!session.complete_client_request(&message["id"], Ok(serde_json::json!({}))));
+    }
+
+    #[test]
+    fn create_message_params_are_sent_in_mcp_shape() {
+        let params = sampling::CreateMessageParams {
+            messages: vec![sampling::SamplingMessage {
+                role: "user".to_string(),
+                content: mcp::Content::Text("Hi".to_string()),
+            }],
+            model_preferences: Some(sampling::ModelPreferences {
+                hints: vec!["claude".to_string()],
+                cost_priority: None,
+                speed_priority: Some(0.5),
+                intelligence_priority: None,
+            }),
+            system_prompt: None,
+            temperature: None,
+            max_tokens: 100,
+            stop_sequences: Vec::new(),
+        };
+
+        assert_eq!(
+            create_message_params_json(params, ProtocolVersion::LATEST).unwrap(),
+            serde_json::json!({
+                "messages": [{ "role": "user", "content": { "type": "text", "text": "Hi" } }],
+                "maxTokens": 100,
+                "modelPreferences": { "hints": [{ "name": "claude" }], "speedPriority": 0.5 },
+            })
+        );
+    }
+
+    #[test]
+    fn create_message_result_parses_the_sampled_message() {
+        let result = create_message_result(serde_json::json!({
+            "role": "assistant",
+            "content": { "type": "text", "text": "Hello" },
+            "model": "test",
+            "stopReason": "endTurn",
+        }))
+        .unwrap();
+
+        assert_eq!(result.role, "assistant");
+        assert_eq!(result.model, "test");
+        assert_eq!(result.stop_reason.as_deref(), Some("endTurn"));
+        assert!(matches!(result.content, mcp::Content::Text(ref text) if text == "Hello"));
+    }
+
+    #[test]
+    fn create_message_result_needs_role_model_and_content() {
+        let result = create_message_result(serde_json::json!({
+            "role": "assistant",
+            "content": { "type": "text", "text": "Hello" },
+        }));
+        assert_eq!(result.unwrap_err().code, INTERNAL_ERROR);
+    }
+
+    #[test]
+    fn parse_content_decodes_media() {
+        let content = serde_json::json!({ "type": "image", "data": "AAEC", "mimeType": "image/png" });
+        let mcp::Content::Image(image) = parse_content(&content).unwrap() else {
+            panic!("expected an image");
+        };
+        assert_eq!(image.data, vec![0, 1, 2]);
+        assert_eq!(image.mime_type, "image/png");
+    }
+
+    #[test]
+    fn parse_content_rejects_unsupported_or_incomplete_parts() {
+        let invalid = [
+            serde_json::json!({ "type": "text" }),
+            serde_json::json!({ "type": "audio", "data": "not base64!", "mimeType": "audio/wav" }),
+            serde_json::json!({ "type": "image", "data": "AAEC" }),
+            serde_json::json!({ "type": "resource", "resource": {} }),
+        ];
+        for content in invalid {
+            assert!(parse_content(&content).is_err(), "{content}");
+        }
+    }
+
+    #[test]
+    fn elicit_params_carry_the_requested_schema() {
+        let schema = r#"{"type":"object","properties":{"name":{"type":"string"}}}"#;
+        let params = elicit_params_json("Your name?".to_string(), schema).unwrap();
+        assert_eq!(params["message"], "Your name?");
+        assert_eq!(params["requestedSchema"]["properties"]["name"]["type"], "string");
+
+        let err = elicit_params_json("Your name?".to_string(), "{").unwrap_err();
+        assert_eq!(err.code, INVALID_PARAMS);
+    }
+
+    #[test]
+    fn elicit_result_maps_each_action() {
+        let accepted = elicit_result(serde_json::json!({ "action": "accept", "content": { "name": "Ada" } })).unwrap();
+        let elicitation::ElicitResult::Accept(content) = accepted else {
+            panic!("expected an accepted elicitation");
+        };
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&content).unwrap()["name"], "Ada");
+
+        let declined = elicit_result(serde_json::json!({ "action": "decline" })).unwrap();
+        assert!(matches!(declined, elicitation::ElicitResult::Decline));
+        let cancelled = elicit_result(serde_json::json!({ "action": "cancel" })).unwrap();
+        assert!(matches!(cancelled, elicitation::ElicitResult::Cancel));
+        assert!(elicit_result(serde_json::json!({ "action": "maybe" })).is_err());
+    }
+
+    #[test]
+    fn accepted_elicitations_without_content_answer_an_empty_object() {
+        let accepted = elicit_result(serde_json::json!({ "action": "accept" })).unwrap();
+        assert!(matches!(accepted, elicitation::ElicitResult::Accept(ref content) if content == "{}"));
+    }
+
+    #[test]
+    fn roots_result_lists_each_root() {
+        let roots = roots_result(serde_json::json!({
+            "roots": [
+                { "uri": "file:///work/app", "name": "app" },
+                { "uri": "file:///work/lib" },
+            ],
+        }))
+        .unwrap();
+
+        assert_eq!(roots.len(), 2);
+        assert_eq!(roots[0].uri, "file:///work/app");
+        assert_eq!(roots[0].name.as_deref(), Some("app"));
+        assert_eq!(roots[1].name, None);
+    }
+
+    #[test]
+    fn roots_result_needs_a_uri_for_every_root() {
+        assert!(roots_result(serde_json::json!({})).is_err());
+        assert!(roots_result(serde_json::json!({ "roots": [{ "name": "app" }] })).is_err());
+    }
+
+    #[tokio::test]
+    async fn roots_are_listed_once_until_they_change() {
+        let (session, mut receiver) = session_supporting("roots");
+        let context = context(&session);
+        let roots = serde_json::json!({ "roots": [{ "uri": "file:///work" }] });
+
+        let client = async {
+            let message = receiver.next().await.unwrap();
+            assert!(session.complete_client_request(&message["id"], Ok(roots.clone())));
+        };
+        let (listed, ()) = futures::join!(context.list_roots(), client);
+        assert_eq!(listed.unwrap(), roots);
+
+        // Served from the session without asking the client again
+        assert_eq!(context.list_roots().await.unwrap(), roots);
+        assert!(receiver.next().now_or_never().is_none());
+
+        session.invalidate_roots();
+        let mut request = Box::pin(context.list_roots());
+        assert!(futures::poll!(request.as_mut()).is_pending());
+        assert_eq!(receiver.next().await.unwrap()["method"], "roots/list");
+    }
+}
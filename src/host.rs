@@ -0,0 +1,173 @@
+//! Host functions imported by MCP components.
+
+use crate::logging::LogLevel;
+use crate::notify::{notification, Notifier};
+use crate::protocol::ProtocolVersion;
+use crate::route::Kind;
+use crate::session::Session;
+use crate::spin::mcp_trigger::{elicitation, logging, notifications, progress, roots, sampling};
+use crate::{client, mcp};
+use crate::sse::MessageSender;
+use std::sync::Arc;
+use tracing::debug;
+
+/// State behind the host functions of one component instance
+pub struct McpInstanceState {
+    component_id: String,
+    notifier: Arc<Notifier>,
+    request: RequestContext,
+}
+
+/// The client request an instance is handling, for host functions that
+/// report back to that client
+#[derive(Clone)]
+pub(crate) struct RequestContext {
+    /// Session the request arrived on, if any
+    pub(crate) session: Option<Arc<Session>>,
+    /// SSE stream the response will be sent on, if the client asked for one
+    pub(crate) stream: Option<MessageSender>,
+    pub(crate) version: ProtocolVersion,
+    /// Token the client wants progress of the request reported under
+    pub(crate) progress_token: Option<serde_json::Value>,
+}
+
+impl RequestContext {
+    pub(crate) fn new(session: Option<&Arc<Session>>, stream: Option<&MessageSender>, version: ProtocolVersion) -> Self {
+        Self {
+            session: session.cloned(),
+            stream: stream.cloned(),
+            version,
+            progress_token: None,
+        }
+    }
+
+    /// Send a message about the request to its client: on the response
+    /// stream while it is open, otherwise on the session's own stream.
+    /// Returns whether the message went anywhere.
+    pub(crate) fn send(&self, message: serde_json::Value) -> bool {
+        if let Some(stream) = self.stream.as_ref().filter(|stream| !stream.is_closed()) {
+            return stream.unbounded_send(message).is_ok();
+        }
+        self.session.as_ref().is_some_and(|session| session.send(message))
+    }
+}
+
+impl Default for RequestContext {
+    /// A context for calls made by the trigger itself, with no client
+    fn default() -> Self {
+        Self::new(None, None, ProtocolVersion::DEFAULT)
+    }
+}
+
+impl McpInstanceState {
+    pub(crate) fn new(component_id: &str, notifier: Arc<Notifier>) -> Self {
+        Self {
+            component_id: component_id.to_string(),
+            notifier,
+            request: RequestContext::default(),
+        }
+    }
+
+    /// Associate the instance with the request it is about to handle
+    pub(crate) fn set_request(&mut self, request: RequestContext) {
+        self.request = request;
+    }
+}
+
+impl notifications::Host for McpInstanceState {
+    async fn notify_resource_updated(&mut self, uri: String) {
+        self.notifier.resource_updated(&self.component_id, &uri);
+    }
+
+    async fn notify_tools_changed(&mut self) {
+        self.notifier.list_changed(&self.component_id, Kind::Tool);
+    }
+
+    async fn notify_resources_changed(&mut self) {
+        self.notifier.list_changed(&self.component_id, Kind::Resource);
+    }
+
+    async fn notify_prompts_changed(&mut self) {
+        self.notifier.list_changed(&self.component_id, Kind::Prompt);
+    }
+}
+
+impl logging::Host for McpInstanceState {
+    async fn log(&mut self, level: logging::Level, logger: Option<String>, data: String) {
+        let level = LogLevel::from(level);
+        debug!(
+            "MCP component {} logged {} from {}: {}",
+            self.component_id,
+            level.as_str(),
+            logger.as_deref().unwrap_or("-"),
+            data
+        );
+
+        // Clients without a session can't pick a level, so get the default
+        let min_level = self.request.session.as_ref().map(|session| session.log_level()).unwrap_or_default();
+        if level < min_level {
+            return;
+        }
+        let mut params = serde_json::json!({ "level": level.as_str(), "data": data });
+        if let Some(logger) = logger {
+            params["logger"] = serde_json::json!(logger);
+        }
+        if !self.request.send(notification("notifications/message", params)) {
+            debug!("No open stream for log records of MCP component {}", self.component_id);
+        }
+    }
+}
+
+impl progress::Host for McpInstanceState {
+    async fn report_progress(&mut self, progress: f64, total: Option<f64>, message: Option<String>) {
+        let Some(token) = &self.request.progress_token else {
+            return;
+        };
+        let mut params = serde_json::json!({ "progressToken": token, "progress": progress });
+        if let Some(total) = total {
+            params["total"] = serde_json::json!(total);
+        }
+        if let Some(message) = message.filter(|_| self.request.version.has_progress_messages()) {
+            params["message"] = serde_json::json!(message);
+        }
+        if !self.request.send(notification("notifications/progress", params)) {
+            debug!("No open stream for progress of MCP component {}", self.component_id);
+        }
+    }
+}
+
+impl sampling::Host for McpInstanceState {
+    async fn create_message(
+        &mut self,
+        params: sampling::CreateMessageParams,
+    ) -> Result<sampling::CreateMessageResult, mcp::Error> {
+        let params = client::create_message_params_json(params, self.request.version)?;
+        let result = self
+            .request
+            .request_client("sampling", "sampling/createMessage", params)
+            .await?;
+        client::create_message_result(result)
+    }
+}
+
+impl elicitation::Host for McpInstanceState {
+    async fn elicit(
+        &mut self,
+        message: String,
+        requested_schema: String,
+    ) -> Result<elicitation::ElicitResult, mcp::Error> {
+        let params = client::elicit_params_json(message, &requested_schema)?;
+        let result = self
+            .request
+            .request_client("elicitation", "elicitation/create", params)
+            .await?;
+        client::elicit_result(result)
+    }
+}
+
+impl roots::Host for McpInstanceState {
+    async fn list_roots(&mut self) -> Result<Vec<roots::Root>, mcp::Error> {
+        let result = self.request.list_roots().await?;
+        client::roots_result(result)
+    }
+}
@@ -1,22 +1,52 @@
 use anyhow::{Context, Result};
 use base64::Engine;
 use clap::Args;
+use futures::future::{Abortable, Aborted};
 use http::{Request as HttpRequest, Response as HttpResponse, StatusCode};
-use http_body_util::{BodyExt, Full};
-use hyper::body::{Bytes, Incoming};
+use http_body_util::BodyExt;
+use hyper::body::Incoming;
 use hyper::server::conn::http1;
 use hyper::service::service_fn;
 use hyper_util::rt::TokioIo;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use spin_app::App;
 use spin_factors::RuntimeFactors;
 use spin_trigger::{Trigger, TriggerApp};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::net::SocketAddr;
-use std::sync::Arc;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::net::TcpListener;
 use tokio::task;
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
+
+mod cache;
+mod capabilities;
+mod client;
+mod host;
+mod logging;
+mod notify;
+mod pool;
+mod protocol;
+mod route;
+mod session;
+mod sse;
+mod stdio;
+
+use cache::ListCache;
+use capabilities::Capabilities;
+pub use host::McpInstanceState;
+use host::RequestContext;
+use logging::LogLevel;
+use notify::Notifier;
+pub use cache::ListCacheMode;
+use pool::{InstancePool, McpInstance};
+use protocol::ProtocolVersion;
+use route::{McpRoute, RouteComponent, RouteOwners, ServerInfo};
+use session::{Session, SessionStore, PROTOCOL_VERSION_HEADER, SESSION_ID_HEADER};
+use sse::{Body, MessageSender};
 
 // Generate bindings from WIT file
 wasmtime::component::bindgen!({
@@ -31,12 +61,18 @@ use spin::mcp_trigger::mcp_types as mcp;
 #[derive(Clone)]
 pub struct McpTrigger {
     listen_addr: SocketAddr,
-    component_routes: HashMap<String, String>,
+    routes: HashMap<String, Arc<McpRoute>>,
+    /// `serverInfo` for routes that don't override it
+    default_server_info: ServerInfo,
+    session_timeout: Duration,
+    instance_pool_size: usize,
+    /// Route served when using the stdio transport
+    stdio_route: Option<Arc<McpRoute>>,
 }
 
 impl<F: RuntimeFactors> Trigger<F> for McpTrigger {
     const TYPE: &'static str = "mcp";
-    type InstanceState = ();
+    type InstanceState = McpInstanceState;
     type CliArgs = CliArgs;
 
     fn new(cli_args: Self::CliArgs, app: &App) -> Result<Self> {
@@ -47,31 +83,148 @@ impl<F: RuntimeFactors> Trigger<F> for McpTrigger {
             .get_trigger_metadata::<TriggerMetadata>(trigger_type)?
             .unwrap_or_default();
 
-        // Collect all MCP trigger configs
-        let mut component_routes = HashMap::new();
+        // Collect all MCP trigger configs. Components sharing a route are
+        // served together as one MCP server.
+        let mut routes: HashMap<String, McpRoute> = HashMap::new();
         let configs = app.trigger_configs::<ComponentConfig>(trigger_type)?;
         
         for (component_id, config) in configs {
             info!("Registering MCP route {} -> component {} (id: {})", config.route, config.component, component_id);
-            component_routes.insert(config.route.clone(), config.component.clone());
+            let route = routes
+                .entry(config.route.clone())
+                .or_insert_with(|| McpRoute {
+                    path: config.route.clone(),
+                    components: Vec::new(),
+                    list_cache: None,
+                    list_page_size: None,
+                    server_info: ServerInfo::default(),
+                });
+            merge_setting(&config.route, "list_cache", &mut route.list_cache, config.list_cache)?;
+            merge_setting(&config.route, "list_page_size", &mut route.list_page_size, config.list_page_size)?;
+            let server_info = &mut route.server_info;
+            merge_setting(&config.route, "server_name", &mut server_info.name, config.server_name)?;
+            merge_setting(&config.route, "server_title", &mut server_info.title, config.server_title)?;
+            merge_setting(&config.route, "server_version", &mut server_info.version, config.server_version)?;
+            merge_setting(&config.route, "instructions", &mut server_info.instructions, config.instructions)?;
+            route.components.push(RouteComponent {
+                id: config.component,
+                prefix: config.prefix,
+            });
         }
 
-        if component_routes.is_empty() {
+        if routes.is_empty() {
             anyhow::bail!("No MCP components found in application");
         }
 
-        info!("Found {} MCP component(s)", component_routes.len());
+        for route in routes.values().filter(|route| route.components.len() > 1) {
+            info!("Route {} aggregates {} components", route.path, route.components.len());
+        }
+        info!("Found {} MCP route(s)", routes.len());
+
+        let routes: HashMap<_, _> = routes
+            .into_iter()
+            .map(|(path, route)| (path, Arc::new(route)))
+            .collect();
+
+        let stdio_route = match cli_args.transport {
+            Transport::Http => None,
+            Transport::Stdio => Some(stdio_route(cli_args.stdio_component.as_deref(), &routes)?),
+        };
+
+        // Routes introduce themselves as the Spin application by default
+        let default_server_info = ServerInfo {
+            name: app.get_metadata(spin_app::APP_NAME_KEY)?,
+            version: app.get_metadata(spin_app::APP_VERSION_KEY)?,
+            ..ServerInfo::default()
+        };
 
         Ok(Self {
             listen_addr: cli_args.address.unwrap_or(metadata.address),
-            component_routes,
+            routes,
+            default_server_info,
+            session_timeout: Duration::from_secs(metadata.session_timeout_secs),
+            instance_pool_size: metadata.instance_pool_size,
+            stdio_route,
         })
     }
 
+    fn add_to_linker(
+        &mut self,
+        linker: &mut spin_core::Linker<spin_factors_executor::InstanceState<F::InstanceState, McpInstanceState>>,
+    ) -> Result<()> {
+        spin::mcp_trigger::notifications::add_to_linker(linker, |state| state.executor_instance_state_mut())?;
+        spin::mcp_trigger::logging::add_to_linker(linker, |state| state.executor_instance_state_mut())?;
+        spin::mcp_trigger::progress::add_to_linker(linker, |state| state.executor_instance_state_mut())?;
+        spin::mcp_trigger::sampling::add_to_linker(linker, |state| state.executor_instance_state_mut())?;
+        spin::mcp_trigger::elicitation::add_to_linker(linker, |state| state.executor_instance_state_mut())?;
+        spin::mcp_trigger::roots::add_to_linker(linker, |state| state.executor_instance_state_mut())
+    }
+
     async fn run(self, trigger_app: TriggerApp<Self, F>) -> Result<()> {
+        let stdio_route = self.stdio_route.clone();
         let server = Arc::new(McpServer::new(self, trigger_app)?);
-        server.serve().await
+        match stdio_route {
+            Some(route) => {
+                server.initialize_components([route.as_ref()]).await?;
+                server.probe_capabilities([route.as_ref()]).await;
+                server.fill_pools();
+                server.serve_stdio(route).await
+            }
+            None => {
+                let routes: Vec<_> = server.trigger.routes.values().cloned().collect();
+                server.initialize_components(routes.iter().map(Arc::as_ref)).await?;
+                server.probe_capabilities(routes.iter().map(Arc::as_ref)).await;
+                server.fill_pools();
+                server.serve().await
+            }
+        }
+    }
+}
+
+/// Pick the route served over stdio. Selecting a component serves just that
+/// component, otherwise the application must have a single route.
+fn stdio_route(
+    component: Option<&str>,
+    routes: &HashMap<String, Arc<McpRoute>>,
+) -> Result<Arc<McpRoute>> {
+    if let Some(component) = component {
+        return routes
+            .values()
+            .find_map(|route| {
+                let selected = route.components.iter().find(|c| c.id == component)?;
+                Some(Arc::new(McpRoute {
+                    path: route.path.clone(),
+                    components: vec![selected.clone()],
+                    list_cache: route.list_cache,
+                    list_page_size: route.list_page_size,
+                    server_info: route.server_info.clone(),
+                }))
+            })
+            .ok_or_else(|| anyhow::anyhow!("No MCP component named {} found in application", component));
+    }
+
+    let mut all_routes = routes.values();
+    match (all_routes.next(), all_routes.next()) {
+        (Some(route), None) => Ok(route.clone()),
+        _ => anyhow::bail!(
+            "Application has multiple MCP routes, select a component with --stdio-component"
+        ),
+    }
+}
+
+/// Apply a route-wide setting from one of the route's components, failing if
+/// another component on the route set it differently
+fn merge_setting<T: PartialEq>(route: &str, name: &str, slot: &mut Option<T>, value: Option<T>) -> Result<()> {
+    let Some(value) = value else {
+        return Ok(());
+    };
+    match slot {
+        Some(existing) if *existing != value => {
+            anyhow::bail!("Components on route {} have conflicting {} settings", route, name)
+        }
+        _ => *slot = Some(value),
     }
+    Ok(())
 }
 
 /// CLI arguments for the MCP trigger
@@ -84,6 +237,23 @@ pub struct CliArgs {
     /// Run a test request against each MCP component
     #[clap(long)]
     pub test: bool,
+
+    /// Transport used to talk to MCP clients
+    #[clap(long, value_enum, default_value = "http", env = "SPIN_MCP_TRANSPORT")]
+    pub transport: Transport,
+
+    /// Component to serve with the stdio transport (defaults to the only MCP component)
+    #[clap(long = "stdio-component")]
+    pub stdio_component: Option<String>,
+}
+
+/// Transport used to talk to MCP clients
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Transport {
+    /// Streamable HTTP on the listen address
+    Http,
+    /// Newline-delimited JSON-RPC over stdin and stdout
+    Stdio,
 }
 
 /// Trigger-level metadata (optional)
@@ -93,12 +263,21 @@ pub struct TriggerMetadata {
     /// Default address to listen on
     #[serde(default = "default_address")]
     pub address: SocketAddr,
+    /// Seconds of inactivity after which a session expires
+    #[serde(default = "default_session_timeout_secs")]
+    pub session_timeout_secs: u64,
+    /// Number of instances of each component to keep ready ahead of
+    /// requests (0 disables pooling)
+    #[serde(default)]
+    pub instance_pool_size: usize,
 }
 
 impl Default for TriggerMetadata {
     fn default() -> Self {
         Self {
             address: default_address(),
+            session_timeout_secs: default_session_timeout_secs(),
+            instance_pool_size: 0,
         }
     }
 }
@@ -107,6 +286,10 @@ fn default_address() -> SocketAddr {
     "127.0.0.1:3000".parse().unwrap()
 }
 
+fn default_session_timeout_secs() -> u64 {
+    30 * 60
+}
+
 /// Per-component configuration
 #[derive(Clone, Debug, Deserialize)]
 #[serde(deny_unknown_fields)]
@@ -115,22 +298,101 @@ pub struct ComponentConfig {
     pub component: String,
     /// The route path (e.g., "/weather")
     pub route: String,
+    /// Prefix for the component's tool and prompt names, to avoid collisions
+    /// when several components share a route (e.g., "weather_")
+    #[serde(default)]
+    pub prefix: Option<String>,
+    /// Cache list results for the route, either for a number of seconds or
+    /// `"until-restart"`
+    #[serde(default)]
+    pub list_cache: Option<ListCacheMode>,
+    /// Have the trigger paginate list results of the route itself, with
+    /// this many entries per page
+    #[serde(default)]
+    pub list_page_size: Option<NonZeroUsize>,
+    /// Server name reported to clients (defaults to the application name)
+    #[serde(default)]
+    pub server_name: Option<String>,
+    /// Human-readable server name shown by clients
+    #[serde(default)]
+    pub server_title: Option<String>,
+    /// Server version reported to clients (defaults to the application version)
+    #[serde(default)]
+    pub server_version: Option<String>,
+    /// Guidance for clients and models on how to use the server
+    #[serde(default)]
+    pub instructions: Option<String>,
 }
 
-/// MCP server that handles HTTP transport
+/// MCP server that handles the Streamable HTTP transport
 struct McpServer<F: RuntimeFactors> {
     trigger: McpTrigger,
     trigger_app: Arc<TriggerApp<McpTrigger, F>>,
+    sessions: Arc<SessionStore>,
+    /// Delivers notifications raised by components to sessions
+    notifier: Arc<Notifier>,
+    /// Owners of tools, prompts and resources on aggregated routes, by route
+    owners: Arc<Mutex<HashMap<String, RouteOwners>>>,
+    /// Warm instances by component, when pooling is enabled
+    pools: HashMap<String, Arc<InstancePool<F>>>,
+    /// List results of routes that opted into caching
+    list_cache: Arc<ListCache>,
+    /// Capabilities advertised on each route, probed at startup
+    capabilities: Mutex<HashMap<String, Capabilities>>,
 }
 
 impl<F: RuntimeFactors> McpServer<F> {
     fn new(trigger: McpTrigger, trigger_app: TriggerApp<McpTrigger, F>) -> Result<Self> {
+        let trigger_app = Arc::new(trigger_app);
+        let sessions = Arc::new(SessionStore::new(trigger.session_timeout));
+        let list_cache = Arc::new(ListCache::default());
+        let owners = Arc::new(Mutex::new(HashMap::new()));
+        let notifier = Arc::new(Notifier::new(
+            sessions.clone(),
+            list_cache.clone(),
+            owners.clone(),
+            &trigger.routes,
+        ));
+
+        let mut pools = HashMap::new();
+        if trigger.instance_pool_size > 0 {
+            for component in trigger.routes.values().flat_map(|route| &route.components) {
+                pools.entry(component.id.clone()).or_insert_with(|| {
+                    Arc::new(InstancePool::new(
+                        &component.id,
+                        trigger.instance_pool_size,
+                        trigger_app.clone(),
+                        notifier.clone(),
+                    ))
+                });
+            }
+        }
+
         Ok(Self {
+            sessions,
+            notifier,
             trigger,
-            trigger_app: Arc::new(trigger_app),
+            trigger_app,
+            owners,
+            pools,
+            list_cache,
+            capabilities: Mutex::new(HashMap::new()),
         })
     }
 
+    /// Start creating warm instances for every pooled component
+    fn fill_pools(&self) {
+        for pool in self.pools.values() {
+            pool.refill();
+        }
+        if !self.pools.is_empty() {
+            info!(
+                "Keeping {} warm instance(s) of each MCP component",
+                self.trigger.instance_pool_size
+            );
+        }
+    }
+
     async fn serve(self: Arc<Self>) -> Result<()> {
         let listener = TcpListener::bind(self.trigger.listen_addr).await?;
         let actual_addr = listener.local_addr()?;
@@ -160,78 +422,355 @@ impl<F: RuntimeFactors> McpServer<F> {
     async fn handle_http_request(
         self: Arc<Self>,
         _client_addr: SocketAddr,
-        mut req: HttpRequest<Incoming>,
-    ) -> Result<HttpResponse<Full<Bytes>>> {
-        let path = req.uri().path();
+        req: HttpRequest<Incoming>,
+    ) -> Result<HttpResponse<Body>> {
+        let path = req.uri().path().to_string();
         
-        // Find component for this route
-        let component_id = self
-            .trigger
-            .component_routes
-            .get(path)
-            .ok_or_else(|| anyhow::anyhow!("No MCP component found for route: {}", path))?;
-
-        // Only accept POST requests
-        if req.method() != http::Method::POST {
+        // Find the components serving this route
+        let Some(route) = self.trigger.routes.get(&path).cloned() else {
+            warn!("No MCP component found for route: {}", path);
             return Ok(HttpResponse::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(sse::empty())?);
+        };
+
+        match *req.method() {
+            http::Method::POST => self.handle_post(route, req).await,
+            http::Method::GET => self.handle_get(&path, &req),
+            http::Method::DELETE => self.handle_delete(&path, &req),
+            _ => Ok(HttpResponse::builder()
                 .status(StatusCode::METHOD_NOT_ALLOWED)
-                .body(Full::new(Bytes::new()))?);
+                .header(http::header::ALLOW, "GET, POST, DELETE")
+                .body(sse::empty())?),
         }
+    }
+
+    /// Handle a JSON-RPC message sent by the client. Requests are answered
+    /// with a single JSON body, or with an SSE stream when the client accepts
+    /// `text/event-stream`.
+    async fn handle_post(
+        self: Arc<Self>,
+        route: Arc<McpRoute>,
+        mut req: HttpRequest<Incoming>,
+    ) -> Result<HttpResponse<Body>> {
+        let stream = sse::accepts_event_stream(req.headers());
 
         // Read request body
         let body_bytes = req.body_mut().collect().await?.to_bytes();
         
-        // Parse JSON-RPC request
-        let json_rpc_request: JsonRpcRequest = serde_json::from_slice(&body_bytes)
-            .context("Failed to parse JSON-RPC request")?;
+        // Parse JSON-RPC request or batch. Bodies that can't be handled at
+        // all are rejected with 400 and a JSON-RPC error explaining why.
+        let payload = JsonRpcPayload::parse(&body_bytes);
+        if let JsonRpcPayload::Single(JsonRpcEntry::Invalid(response)) = &payload {
+            warn!("Rejecting malformed JSON-RPC message: {:?}", response.error);
+            return Ok(HttpResponse::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .header("content-type", "application/json")
+                .body(sse::full(serde_json::to_vec(response)?))?);
+        }
+
+        // `initialize` starts a new session, everything else joins the
+        // session named in the header. Clients that never initialize are
+        // served without a session.
+        let initialize = payload.is_initialize();
+        let session = if initialize {
+            Some(Arc::new(Session::new(&route.path)))
+        } else {
+            match self.sessions.lookup(&route.path, req.headers()) {
+                Ok(session) => session,
+                Err(status) => {
+                    return Ok(HttpResponse::builder().status(status).body(sse::empty())?)
+                }
+            }
+        };
+
+        // Requests outside a session name their protocol version in a header
+        let version = match &session {
+            Some(session) => session.protocol_version(),
+            None => match request_protocol_version(&req) {
+                Ok(version) => version,
+                Err(status) => {
+                    return Ok(HttpResponse::builder().status(status).body(sse::empty())?)
+                }
+            },
+        };
+
+        // Notifications (no id) are accepted without a response body
+        if !payload.expects_response() {
+            self.handle_payload(&route, session.as_ref(), None, version, payload).await?;
+            return Ok(HttpResponse::builder()
+                .status(StatusCode::ACCEPTED)
+                .body(sse::empty())?);
+        }
+
+        // The session ID header has to be known up front, so `initialize`
+        // is always answered with a plain JSON body
+        if stream && !initialize {
+            // Answer on an SSE stream that closes once the response is sent
+            let (sender, receiver) = sse::channel();
+            task::spawn(async move {
+                match self.handle_payload(&route, session.as_ref(), Some(&sender), version, payload).await {
+                    Ok(Some(reply)) => {
+                        let _ = sender.unbounded_send(reply);
+                    }
+                    Ok(None) => {}
+                    Err(err) => warn!("Error handling MCP request: {err:?}"),
+                }
+            });
+            return sse::response(receiver);
+        }
 
         // Handle the request
-        let response = self
-            .handle_mcp_request(component_id, json_rpc_request)
+        let reply = self
+            .handle_payload(&route, session.as_ref(), None, version, payload)
             .await?;
 
-        // For notifications (no id), return empty response
-        if response.is_none() {
+        let Some(reply) = reply else {
             return Ok(HttpResponse::builder()
-                .status(StatusCode::NO_CONTENT)
-                .body(Full::new(Bytes::new()))?);
+                .status(StatusCode::ACCEPTED)
+                .body(sse::empty())?);
+        };
+
+        let mut builder = HttpResponse::builder()
+            .status(StatusCode::OK)
+            .header("content-type", "application/json");
+
+        // Only a successful handshake establishes the session
+        if let Some(session) = session.filter(|_| initialize && reply.get("error").is_none()) {
+            info!("Started MCP session {} on route {}", session.id(), route.path);
+            builder = builder.header(SESSION_ID_HEADER, session.id());
+            self.sessions.insert(session);
         }
 
         // Serialize response
-        let response_bytes = serde_json::to_vec(&response.unwrap())?;
+        let response_bytes = serde_json::to_vec(&reply)?;
         
-        Ok(HttpResponse::builder()
-            .status(StatusCode::OK)
-            .header("content-type", "application/json")
-            .body(Full::new(response_bytes.into()))?)
+        Ok(builder.body(sse::full(response_bytes))?)
+    }
+
+    /// Open a server-to-client SSE stream for messages not tied to a request
+    fn handle_get(&self, route: &str, req: &HttpRequest<Incoming>) -> Result<HttpResponse<Body>> {
+        if !sse::accepts_event_stream(req.headers()) {
+            return Ok(HttpResponse::builder()
+                .status(StatusCode::NOT_ACCEPTABLE)
+                .body(sse::empty())?);
+        }
+
+        // Server-initiated messages are delivered per session
+        let session = match self.sessions.lookup(route, req.headers()) {
+            Ok(Some(session)) => session,
+            Ok(None) => {
+                return Ok(HttpResponse::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .body(sse::empty())?)
+            }
+            Err(status) => return Ok(HttpResponse::builder().status(status).body(sse::empty())?),
+        };
+
+        let (sender, receiver) = sse::channel();
+        session.add_stream(sender);
+
+        info!("Opened SSE stream for MCP session {}", session.id());
+        sse::response(receiver)
+    }
+
+    /// Terminate the session named in the request header
+    fn handle_delete(&self, route: &str, req: &HttpRequest<Incoming>) -> Result<HttpResponse<Body>> {
+        // Sessions can only be ended on the route they were started on
+        let status = match self.sessions.lookup(route, req.headers()) {
+            Ok(Some(session)) if self.sessions.remove(session.id()) => {
+                info!("Terminated MCP session {}", session.id());
+                StatusCode::OK
+            }
+            Ok(Some(_)) => StatusCode::NOT_FOUND,
+            Ok(None) => StatusCode::BAD_REQUEST,
+            Err(status) => status,
+        };
+        Ok(HttpResponse::builder().status(status).body(sse::empty())?)
+    }
+
+    /// Handle a single message or a batch. Returns the reply to send back:
+    /// a response, an array of responses, or nothing when only
+    /// notifications were received.
+    async fn handle_payload(
+        &self,
+        route: &McpRoute,
+        session: Option<&Arc<Session>>,
+        stream: Option<&MessageSender>,
+        version: ProtocolVersion,
+        payload: JsonRpcPayload,
+    ) -> Result<Option<serde_json::Value>> {
+        let entries = match payload {
+            JsonRpcPayload::Single(JsonRpcEntry::Request(request)) => {
+                let response = self.handle_mcp_request(route, session, stream, version, request).await;
+                return Ok(response.map(serde_json::to_value).transpose()?);
+            }
+            JsonRpcPayload::Single(JsonRpcEntry::Response(response)) => {
+                self.handle_client_response(session, response);
+                return Ok(None);
+            }
+            JsonRpcPayload::Single(JsonRpcEntry::Invalid(response)) => {
+                return Ok(Some(serde_json::to_value(response)?))
+            }
+            JsonRpcPayload::Batch(entries) => entries,
+        };
+
+        if entries.is_empty() {
+            let response = JsonRpcResponse::error(serde_json::Value::Null, INVALID_REQUEST, "Invalid Request", None);
+            return Ok(Some(serde_json::to_value(response)?));
+        }
+
+        // Entries are independent, so dispatch them concurrently
+        let responses = futures::future::join_all(entries.into_iter().map(|entry| async move {
+            let request = match entry {
+                JsonRpcEntry::Request(request) => request,
+                JsonRpcEntry::Response(response) => {
+                    self.handle_client_response(session, response);
+                    return None;
+                }
+                JsonRpcEntry::Invalid(response) => return Some(response),
+            };
+            // The handshake establishes the session, so it has to be sent on its own
+            if request.method == "initialize" {
+                return request.id.map(|id| {
+                    JsonRpcResponse::error(id, INVALID_REQUEST, "initialize must not be part of a batch", None)
+                });
+            }
+            self.handle_mcp_request(route, session, stream, version, request).await
+        }))
+        .await;
+
+        // Notifications get no entry in the reply
+        let responses: Vec<_> = responses.into_iter().flatten().collect();
+        if responses.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::to_value(responses)?))
+    }
+
+    /// Pass the client's reply to a request the server sent it on to the
+    /// component waiting for it
+    fn handle_client_response(&self, session: Option<&Arc<Session>>, response: JsonRpcResponse) {
+        let reply = match response.error {
+            Some(err) => Err(mcp::Error {
+                code: err.code,
+                message: err.message,
+                data: err.data.map(|data| match data {
+                    serde_json::Value::String(data) => data,
+                    data => data.to_string(),
+                }),
+            }),
+            None => Ok(response.result.unwrap_or_default()),
+        };
+        if !session.is_some_and(|session| session.complete_client_request(&response.id, reply)) {
+            warn!("Ignoring client response {}, no request is waiting for it", response.id);
+        }
     }
 
+    /// Handle one JSON-RPC message. Failures are reported to the client as
+    /// JSON-RPC errors, or only logged for notifications.
     async fn handle_mcp_request(
         &self,
-        component_id: &str,
+        route: &McpRoute,
+        session: Option<&Arc<Session>>,
+        stream: Option<&MessageSender>,
+        version: ProtocolVersion,
+        json_rpc_request: JsonRpcRequest,
+    ) -> Option<JsonRpcResponse> {
+        let id = json_rpc_request.id.clone();
+        let method = json_rpc_request.method.clone();
+
+        let dispatch = self.dispatch_mcp_request(route, session, stream, version, json_rpc_request);
+        let result = match (session, &id) {
+            // Requests in a session can be cancelled until they complete.
+            // Aborting drops the component call with its instance; Spin makes
+            // long-running guests yield, so even busy components stop.
+            (Some(session), Some(request_id)) => {
+                let Some(registration) = session.track_request(request_id) else {
+                    // Cancellations name requests by ID, so IDs must be unique
+                    return Some(JsonRpcResponse::error(
+                        request_id.clone(),
+                        INVALID_REQUEST,
+                        "Request ID is already in use by a request in flight",
+                        None,
+                    ));
+                };
+                let result = Abortable::new(dispatch, registration).await;
+                session.finish_request(request_id);
+                match result {
+                    Ok(result) => result,
+                    Err(Aborted) => {
+                        info!("MCP request {} ({}) was cancelled", request_id, method);
+                        // The client has stopped waiting, so there is no reply
+                        return None;
+                    }
+                }
+            }
+            _ => dispatch.await,
+        };
+
+        match result {
+            Ok(response) => response,
+            Err(err) => {
+                warn!("Error handling MCP request {}: {:?}", method, err);
+                id.map(|id| err.into_response(id))
+            }
+        }
+    }
+
+    async fn dispatch_mcp_request(
+        &self,
+        route: &McpRoute,
+        session: Option<&Arc<Session>>,
+        stream: Option<&MessageSender>,
+        version: ProtocolVersion,
         json_rpc_request: JsonRpcRequest,
-    ) -> Result<Option<JsonRpcResponse>> {
+    ) -> std::result::Result<Option<JsonRpcResponse>, RequestError> {
+        let mut context = RequestContext::new(session, stream, version);
+
         // Convert JSON-RPC method to MCP request type
         let mcp_request = match json_rpc_request.method.as_str() {
-            "tools/list" => mcp::Request::ToolsList,
+            "tools/list" => {
+                let params: ListParams = parse_params(json_rpc_request.params)?;
+                mcp::Request::ToolsList(mcp::ListParams { cursor: params.cursor })
+            }
             "tools/call" => {
-                let params: ToolsCallParams = serde_json::from_value(json_rpc_request.params.unwrap_or_default())?;
+                let params: ToolsCallParams = parse_params(json_rpc_request.params)?;
+                context.progress_token = params.meta.progress_token;
                 mcp::Request::ToolsCall(mcp::ToolsCallParams {
                     name: params.name,
-                    arguments: serde_json::to_string(&params.arguments)?,
+                    arguments: params.arguments.to_string(),
+                    // Components only need to know whether to report
+                    progress_token: context.progress_token.as_ref().map(|token| match token {
+                        serde_json::Value::String(token) => token.clone(),
+                        token => token.to_string(),
+                    }),
                 })
             }
-            "resources/list" => mcp::Request::ResourcesList,
+            "resources/list" => {
+                let params: ListParams = parse_params(json_rpc_request.params)?;
+                mcp::Request::ResourcesList(mcp::ListParams { cursor: params.cursor })
+            }
+            "resources/templates/list" => {
+                let params: ListParams = parse_params(json_rpc_request.params)?;
+                mcp::Request::ResourcesTemplatesList(mcp::ListParams { cursor: params.cursor })
+            }
             "resources/read" => {
-                let params: ResourceReadParams = serde_json::from_value(json_rpc_request.params.unwrap_or_default())?;
+                let params: ResourceReadParams = parse_params(json_rpc_request.params)?;
                 mcp::Request::ResourcesRead(mcp::ResourceReadParams { uri: params.uri })
             }
-            "prompts/list" => mcp::Request::PromptsList,
+            "prompts/list" => {
+                let params: ListParams = parse_params(json_rpc_request.params)?;
+                mcp::Request::PromptsList(mcp::ListParams { cursor: params.cursor })
+            }
+            "resources/subscribe" | "resources/unsubscribe" => {
+                return self.handle_subscription(route, &context, json_rpc_request).await;
+            }
             "prompts/get" => {
-                let params: PromptGetParams = serde_json::from_value(json_rpc_request.params.unwrap_or_default())?;
+                let params: PromptGetParams = parse_params(json_rpc_request.params)?;
                 mcp::Request::PromptsGet(mcp::PromptGetParams {
                     name: params.name,
-                    arguments: serde_json::to_string(&params.arguments)?,
+                    arguments: params.arguments.to_string(),
                 })
             }
             "ping" => mcp::Request::Ping,
@@ -240,21 +779,39 @@ impl<F: RuntimeFactors> McpServer<F> {
                 info!("Handling initialize request");
                 // Initialize requests must have an ID
                 if let Some(id) = json_rpc_request.id {
-                    return Ok(Some(JsonRpcResponse::success(
-                        id,
-                        serde_json::json!({
-                            "protocolVersion": "2025-03-26",
-                            "capabilities": {
-                                "tools": {},
-                                "resources": {},
-                                "prompts": {}
-                            },
-                            "serverInfo": {
-                                "name": "spin-mcp-server",
-                                "version": "0.1.0"
-                            }
-                        })
-                    )));
+                    let params: InitializeParams = match json_rpc_request.params {
+                        Some(params) => parse_params(Some(params))?,
+                        None => InitializeParams::default(),
+                    };
+                    let version = ProtocolVersion::negotiate(params.protocol_version.as_deref());
+                    if params.protocol_version.as_deref() != Some(version.as_str()) {
+                        info!(
+                            "Client requested protocol version {:?}, offering {}",
+                            params.protocol_version, version
+                        );
+                    }
+                    if let Some(session) = session {
+                        session.set_initialized(version, params.capabilities);
+                    }
+                    let server_info = &route.server_info;
+                    let defaults = &self.trigger.default_server_info;
+                    let name = server_info.name.as_ref().or(defaults.name.as_ref());
+                    let server_version = server_info.version.as_ref().or(defaults.version.as_ref());
+                    let mut result = serde_json::json!({
+                        "protocolVersion": version.as_str(),
+                        "capabilities": self.route_capabilities(route).to_json(),
+                        "serverInfo": {
+                            "name": name.map_or(DEFAULT_SERVER_NAME, String::as_str),
+                            "version": server_version.map_or(DEFAULT_SERVER_VERSION, String::as_str),
+                        }
+                    });
+                    if let Some(title) = server_info.title.as_ref().filter(|_| version.has_titles()) {
+                        result["serverInfo"]["title"] = serde_json::json!(title);
+                    }
+                    if let Some(instructions) = &server_info.instructions {
+                        result["instructions"] = serde_json::json!(instructions);
+                    }
+                    return Ok(Some(JsonRpcResponse::success(id, result)));
                 } else {
                     return Ok(None);
                 }
@@ -264,42 +821,46 @@ impl<F: RuntimeFactors> McpServer<F> {
                 info!("Client initialized notification received");
                 return Ok(None); // Notifications don't get responses
             }
-            "notifications/tools/list_changed" => {
-                // Client notifying that tool list has changed
-                info!("Tools list changed notification received");
-                return Ok(None); // Notifications don't get responses
-            }
-            // Method stubs for unimplemented features
-            "resources/templates/list" => {
-                info!("Resource templates list requested (not implemented)");
-                if let Some(id) = json_rpc_request.id {
-                    return Ok(Some(JsonRpcResponse::success(
-                        id,
-                        serde_json::json!({ "resourceTemplates": [] })
-                    )));
+            "notifications/cancelled" => {
+                let params: CancelledParams = parse_params(json_rpc_request.params)?;
+                let cancelled = session.is_some_and(|session| session.cancel_request(&params.request_id));
+                if cancelled {
+                    info!(
+                        "Cancelling MCP request {}: {}",
+                        params.request_id,
+                        params.reason.as_deref().unwrap_or("no reason given")
+                    );
+                } else {
+                    // The request may have completed already
+                    debug!("Ignoring cancellation of unknown MCP request {}", params.request_id);
                 }
                 return Ok(None);
             }
-            "resources/subscribe" | "resources/unsubscribe" => {
-                info!("Resource subscription requested (not implemented)");
-                if let Some(id) = json_rpc_request.id {
-                    return Ok(Some(JsonRpcResponse::success(
-                        id,
-                        serde_json::json!({})
-                    )));
+            "notifications/roots/list_changed" => {
+                // Components list the roots again the next time they ask
+                if let Some(session) = session {
+                    session.invalidate_roots();
                 }
                 return Ok(None);
             }
+            "notifications/tools/list_changed" => {
+                // Client notifying that tool list has changed
+                info!("Tools list changed notification received");
+                return Ok(None); // Notifications don't get responses
+            }
             "logging/setLevel" => {
-                info!("Logging level change requested (not implemented)");
-                if let Some(id) = json_rpc_request.id {
-                    return Ok(Some(JsonRpcResponse::success(
-                        id,
-                        serde_json::json!({})
-                    )));
-                }
-                return Ok(None);
+                let Some(session) = session else {
+                    return Ok(json_rpc_request.id.map(|id| {
+                        JsonRpcResponse::error(id, INVALID_REQUEST, "Setting a log level requires a session", None)
+                    }));
+                };
+                let params: SetLevelParams = parse_params(json_rpc_request.params)?;
+                session.set_log_level(params.level);
+                return Ok(json_rpc_request
+                    .id
+                    .map(|id| JsonRpcResponse::success(id, serde_json::json!({}))));
             }
+            // Method stubs for unimplemented features
             "completion/complete" => {
                 info!("Completion requested (not implemented)");
                 if let Some(id) = json_rpc_request.id {
@@ -316,28 +877,6 @@ impl<F: RuntimeFactors> McpServer<F> {
                 }
                 return Ok(None);
             }
-            "roots/list" => {
-                info!("Roots list requested (not implemented)");
-                if let Some(id) = json_rpc_request.id {
-                    return Ok(Some(JsonRpcResponse::success(
-                        id,
-                        serde_json::json!({ "roots": [] })
-                    )));
-                }
-                return Ok(None);
-            }
-            "sampling/createMessage" => {
-                info!("Sampling requested (not implemented)");
-                if let Some(id) = json_rpc_request.id {
-                    return Ok(Some(JsonRpcResponse::error(
-                        id,
-                        -32601,
-                        "Sampling not implemented",
-                        None,
-                    )));
-                }
-                return Ok(None);
-            }
             // Handle other notifications
             method if method.starts_with("notifications/") => {
                 info!("Received notification: {}", method);
@@ -348,7 +887,7 @@ impl<F: RuntimeFactors> McpServer<F> {
                 if let Some(id) = json_rpc_request.id {
                     return Ok(Some(JsonRpcResponse::error(
                         id,
-                        -32601,
+                        METHOD_NOT_FOUND,
                         &format!("Method not found: {}", json_rpc_request.method),
                         None,
                     )));
@@ -359,28 +898,36 @@ impl<F: RuntimeFactors> McpServer<F> {
             }
         };
 
-        // Prepare and instantiate the component
-        let instance_builder = self.trigger_app.prepare(component_id)?;
-        let (instance, mut store) = instance_builder.instantiate(()).await?;
-        let instance = SpinMcp::new(&mut store, &instance)?;
-
-        // Call the component's handler
-        let mcp_response = instance
-            .call_handle_request(&mut store, &mcp_request)
-            .await?;
+        // Call the component(s) serving the route
+        let mcp_response = self.call_route(route, mcp_request, &context).await?;
 
         // All component responses need an ID
         if let Some(id) = json_rpc_request.id {
             let json_rpc_response = match mcp_response {
-                mcp::Response::ToolsList(tools) => {
-                    let tools_json: Vec<_> = tools.into_iter().map(|t| {
-                        serde_json::json!({
+                mcp::Response::ToolsList(page) => {
+                    let tools_json: Vec<_> = page.tools.into_iter().map(|t| {
+                        let mut tool = serde_json::json!({
                             "name": t.name,
                             "description": t.description,
                             "inputSchema": serde_json::from_str::<serde_json::Value>(&t.input_schema).unwrap_or(serde_json::json!({})),
-                        })
+                        });
+                        if let Some(title) = t.title.filter(|_| version.has_titles()) {
+                            tool["title"] = serde_json::json!(title);
+                        }
+                        if let Some(annotations) = t.annotations.filter(|_| version.has_tool_annotations()) {
+                            tool["annotations"] = tool_annotations_json(annotations);
+                        }
+                        // Clients that can't receive structured results
+                        // have no use for their schema
+                        if let Some(output_schema) = t.output_schema.filter(|_| version.has_structured_content()) {
+                            match serde_json::from_str::<serde_json::Value>(&output_schema) {
+                                Ok(output_schema) => tool["outputSchema"] = output_schema,
+                                Err(err) => warn!("Ignoring invalid output schema of tool {}: {}", t.name, err),
+                            }
+                        }
+                        tool
                     }).collect();
-                    JsonRpcResponse::success(id.clone(), serde_json::json!({ "tools": tools_json }))
+                    JsonRpcResponse::success(id.clone(), list_result("tools", tools_json, page.next_cursor))
                 }
                 mcp::Response::ToolsCall(result) => {
                     match result {
@@ -394,24 +941,39 @@ impl<F: RuntimeFactors> McpServer<F> {
                         }
                         mcp::ToolResult::Json(json_str) => {
                             // Validate JSON but return original string
-                            let _json_value: serde_json::Value = serde_json::from_str(&json_str)?;
-                            JsonRpcResponse::success(id.clone(), serde_json::json!({ 
+                            let json_value: serde_json::Value = serde_json::from_str(&json_str)
+                                .context("Component returned invalid JSON tool result")?;
+                            let mut result = serde_json::json!({ 
                                 "content": [{
                                     "type": "text",
                                     "text": json_str
                                 }]
-                            }))
+                            });
+                            // Newer clients also get the value itself, which
+                            // must be a JSON object
+                            if version.has_structured_content() && json_value.is_object() {
+                                result["structuredContent"] = json_value;
+                            }
+                            JsonRpcResponse::success(id.clone(), result)
                         }
                         mcp::ToolResult::Binary(bytes) => {
-                            use base64::Engine;
-                            let base64 = base64::engine::general_purpose::STANDARD.encode(&bytes);
-                            JsonRpcResponse::success(id.clone(), serde_json::json!({ 
-                                "content": [{
-                                    "type": "image",
-                                    "data": base64,
-                                    "mimeType": "application/octet-stream"
-                                }]
-                            }))
+                            // Untyped bytes aren't an image, so they go out
+                            // as an embedded resource
+                            let contents = mcp::ResourceContents {
+                                uri: BINARY_RESULT_URI.to_string(),
+                                mime_type: Some("application/octet-stream".to_string()),
+                                text: None,
+                                blob: Some(bytes),
+                            };
+                            let content = content_json(mcp::Content::EmbeddedResource(contents), version);
+                            JsonRpcResponse::success(id.clone(), serde_json::json!({ "content": [content] }))
+                        }
+                        mcp::ToolResult::Content(parts) => {
+                            let content: Vec<_> = parts
+                                .into_iter()
+                                .map(|part| content_json(part, version))
+                                .collect();
+                            JsonRpcResponse::success(id.clone(), serde_json::json!({ "content": content }))
                         }
                         mcp::ToolResult::Error(err) => {
                             // For errors, we return isError: true with content array
@@ -425,8 +987,8 @@ impl<F: RuntimeFactors> McpServer<F> {
                         }
                     }
                 }
-                mcp::Response::ResourcesList(resources) => {
-                    let resources_json: Vec<_> = resources.into_iter().map(|r| {
+                mcp::Response::ResourcesList(page) => {
+                    let resources_json: Vec<_> = page.resources.into_iter().map(|r| {
                         serde_json::json!({
                             "uri": r.uri,
                             "name": r.name,
@@ -434,34 +996,27 @@ impl<F: RuntimeFactors> McpServer<F> {
                             "mimeType": r.mime_type,
                         })
                     }).collect();
-                    JsonRpcResponse::success(id.clone(), serde_json::json!({ "resources": resources_json }))
+                    JsonRpcResponse::success(id.clone(), list_result("resources", resources_json, page.next_cursor))
                 }
-                mcp::Response::ResourcesRead(contents) => {
-                    // MCP spec expects contents to be an array
-                    let content_item = if let Some(text) = contents.text {
-                        serde_json::json!({
-                            "uri": contents.uri,
-                            "mimeType": contents.mime_type,
-                            "text": text
-                        })
-                    } else if let Some(blob) = contents.blob {
+                mcp::Response::ResourcesTemplatesList(page) => {
+                    let templates_json: Vec<_> = page.resource_templates.into_iter().map(|t| {
                         serde_json::json!({
-                            "uri": contents.uri,
-                            "mimeType": contents.mime_type,
-                            "blob": base64::engine::general_purpose::STANDARD.encode(&blob)
-                        })
-                    } else {
-                        serde_json::json!({
-                            "uri": contents.uri,
-                            "mimeType": contents.mime_type
+                            "uriTemplate": t.uri_template,
+                            "name": t.name,
+                            "description": t.description,
+                            "mimeType": t.mime_type,
                         })
-                    };
+                    }).collect();
+                    JsonRpcResponse::success(id.clone(), list_result("resourceTemplates", templates_json, page.next_cursor))
+                }
+                mcp::Response::ResourcesRead(contents) => {
+                    // MCP spec expects contents to be an array
                     JsonRpcResponse::success(id.clone(), serde_json::json!({
-                        "contents": [content_item]
+                        "contents": [resource_contents_json(contents)]
                     }))
                 }
-                mcp::Response::PromptsList(prompts) => {
-                    let prompts_json: Vec<_> = prompts.into_iter().map(|p| {
+                mcp::Response::PromptsList(page) => {
+                    let prompts_json: Vec<_> = page.prompts.into_iter().map(|p| {
                         serde_json::json!({
                             "name": p.name,
                             "description": p.description,
@@ -474,7 +1029,7 @@ impl<F: RuntimeFactors> McpServer<F> {
                             }).collect::<Vec<_>>(),
                         })
                     }).collect();
-                    JsonRpcResponse::success(id.clone(), serde_json::json!({ "prompts": prompts_json }))
+                    JsonRpcResponse::success(id.clone(), list_result("prompts", prompts_json, page.next_cursor))
                 }
                 mcp::Response::PromptsGet(messages) => {
                     let messages_json: Vec<_> = messages.into_iter().map(|m| {
@@ -502,12 +1057,357 @@ impl<F: RuntimeFactors> McpServer<F> {
             Ok(None)
         }
     }
+
+    /// Record a session's interest in a resource and let the component that
+    /// owns it know. Components that don't handle subscriptions can still
+    /// report updates, so their "method not found" is not an error.
+    async fn handle_subscription(
+        &self,
+        route: &McpRoute,
+        context: &RequestContext,
+        json_rpc_request: JsonRpcRequest,
+    ) -> std::result::Result<Option<JsonRpcResponse>, RequestError> {
+        let subscribe = json_rpc_request.method == "resources/subscribe";
+        let Some(session) = &context.session else {
+            return Ok(json_rpc_request.id.map(|id| {
+                JsonRpcResponse::error(id, INVALID_REQUEST, "Resource subscriptions require a session", None)
+            }));
+        };
+
+        let params: ResourceReadParams = parse_params(json_rpc_request.params)?;
+        let request = if subscribe {
+            mcp::Request::ResourcesSubscribe(mcp::ResourceSubscribeParams { uri: params.uri.clone() })
+        } else {
+            mcp::Request::ResourcesUnsubscribe(mcp::ResourceUnsubscribeParams { uri: params.uri.clone() })
+        };
+
+        match self.call_route(route, request, context).await? {
+            mcp::Response::Error(err) if err.code != METHOD_NOT_FOUND => {
+                return Ok(json_rpc_request
+                    .id
+                    .map(|id| JsonRpcResponse::error(id, err.code, &err.message, err.data)));
+            }
+            _ => {}
+        }
+
+        if subscribe {
+            session.subscribe(&params.uri);
+        } else {
+            session.unsubscribe(&params.uri);
+        }
+        Ok(json_rpc_request
+            .id
+            .map(|id| JsonRpcResponse::success(id, serde_json::json!({}))))
+    }
+
+    /// Call the `initialize` export of every component on the given routes,
+    /// failing if any of them reports an error
+    async fn initialize_components<'a>(&self, routes: impl IntoIterator<Item = &'a McpRoute>) -> Result<()> {
+        let mut initialized = HashSet::new();
+        for route in routes {
+            for component in &route.components {
+                if !initialized.insert(component.id.as_str()) {
+                    continue;
+                }
+
+                let mut instance = pool::instantiate(&self.trigger_app, &component.id, &self.notifier).await?;
+                instance
+                    .bindings
+                    .call_initialize(&mut instance.store)
+                    .await
+                    .with_context(|| format!("Failed to call initialize on MCP component {}", component.id))?
+                    .map_err(|err| anyhow::anyhow!("MCP component {} failed to initialize: {}", component.id, err))?;
+
+                info!("Initialized MCP component {}", component.id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Call a component's request handler on a fresh instance
+    async fn call_component(
+        &self,
+        component_id: &str,
+        request: &mcp::Request,
+        context: &RequestContext,
+    ) -> Result<mcp::Response> {
+        let mut instance = self.instance(component_id).await?;
+        instance
+            .store
+            .data_mut()
+            .executor_instance_state_mut()
+            .set_request(context.clone());
+
+        // Call the component's handler
+        instance
+            .bindings
+            .call_handle_request(&mut instance.store, request)
+            .await
+    }
+
+    /// Get an instance of a component, from its warm pool if it has one
+    async fn instance(&self, component_id: &str) -> Result<McpInstance<F>> {
+        if let Some(instance) = self.pools.get(component_id).and_then(|pool| pool.take()) {
+            return Ok(instance);
+        }
+        pool::instantiate(&self.trigger_app, component_id, &self.notifier).await
+    }
+}
+
+/// The protocol version named in a request's header, or the default when
+/// there is none. Unsupported versions are rejected with 400.
+fn request_protocol_version<B>(req: &HttpRequest<B>) -> std::result::Result<ProtocolVersion, StatusCode> {
+    match req.headers().get(PROTOCOL_VERSION_HEADER) {
+        Some(version) => version
+            .to_str()
+            .ok()
+            .and_then(ProtocolVersion::parse)
+            .ok_or(StatusCode::BAD_REQUEST),
+        None => Ok(ProtocolVersion::DEFAULT),
+    }
+}
+
+/// The result of a list method, with `nextCursor` when there are more pages
+fn list_result(key: &str, entries: Vec<serde_json::Value>, next_cursor: Option<String>) -> serde_json::Value {
+    let mut result = serde_json::json!({ key: entries });
+    if let Some(next_cursor) = next_cursor {
+        result["nextCursor"] = serde_json::json!(next_cursor);
+    }
+    result
+}
+
+/// Map resource contents to MCP JSON, with binary contents base64 encoded
+fn resource_contents_json(contents: mcp::ResourceContents) -> serde_json::Value {
+    if let Some(text) = contents.text {
+        serde_json::json!({
+            "uri": contents.uri,
+            "mimeType": contents.mime_type,
+            "text": text
+        })
+    } else if let Some(blob) = contents.blob {
+        serde_json::json!({
+            "uri": contents.uri,
+            "mimeType": contents.mime_type,
+            "blob": base64::engine::general_purpose::STANDARD.encode(&blob)
+        })
+    } else {
+        serde_json::json!({
+            "uri": contents.uri,
+            "mimeType": contents.mime_type
+        })
+    }
+}
+
+/// Map one part of a tool result to MCP JSON. Parts the negotiated protocol
+/// version doesn't know are replaced with text describing them.
+pub(crate) fn content_json(part: mcp::Content, version: ProtocolVersion) -> serde_json::Value {
+    let base64 = &base64::engine::general_purpose::STANDARD;
+    match part {
+        mcp::Content::Text(text) => serde_json::json!({
+            "type": "text",
+            "text": text
+        }),
+        mcp::Content::Image(image) => serde_json::json!({
+            "type": "image",
+            "data": base64.encode(&image.data),
+            "mimeType": image.mime_type
+        }),
+        mcp::Content::Audio(audio) if version.has_audio() => serde_json::json!({
+            "type": "audio",
+            "data": base64.encode(&audio.data),
+            "mimeType": audio.mime_type
+        }),
+        mcp::Content::Audio(audio) => serde_json::json!({
+            "type": "text",
+            "text": format!("[{} audio not supported by protocol version {}]", audio.mime_type, version)
+        }),
+        mcp::Content::EmbeddedResource(contents) => serde_json::json!({
+            "type": "resource",
+            "resource": resource_contents_json(contents)
+        }),
+        mcp::Content::ResourceLink(link) if version.has_resource_links() => serde_json::json!({
+            "type": "resource_link",
+            "uri": link.uri,
+            "name": link.name,
+            "description": link.description,
+            "mimeType": link.mime_type
+        }),
+        mcp::Content::ResourceLink(link) => serde_json::json!({
+            "type": "text",
+            "text": format!("{}: {}", link.name, link.uri)
+        }),
+    }
+}
+
+/// Map tool annotations to MCP JSON, leaving out unset hints
+fn tool_annotations_json(annotations: mcp::ToolAnnotations) -> serde_json::Value {
+    let hints = [
+        ("title", annotations.title.map(serde_json::Value::String)),
+        ("readOnlyHint", annotations.read_only_hint.map(serde_json::Value::Bool)),
+        ("destructiveHint", annotations.destructive_hint.map(serde_json::Value::Bool)),
+        ("idempotentHint", annotations.idempotent_hint.map(serde_json::Value::Bool)),
+        ("openWorldHint", annotations.open_world_hint.map(serde_json::Value::Bool)),
+    ];
+    hints
+        .into_iter()
+        .filter_map(|(key, value)| Some((key.to_string(), value?)))
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+/// URI of the embedded resource a `binary` tool result is sent as, since
+/// the bytes don't come from a resource of their own
+const BINARY_RESULT_URI: &str = "tool-result:binary";
+
+// JSON-RPC error codes
+const PARSE_ERROR: i32 = -32700;
+pub(crate) const INVALID_REQUEST: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+pub(crate) const INVALID_PARAMS: i32 = -32602;
+pub(crate) const INTERNAL_ERROR: i32 = -32603;
+
+/// Failure while handling a JSON-RPC request
+#[derive(Debug)]
+enum RequestError {
+    /// The request params don't match what the method expects
+    InvalidParams(serde_json::Error),
+    /// The component could not be instantiated, trapped, or returned
+    /// something that can't be mapped to MCP
+    Internal(anyhow::Error),
+}
+
+impl From<anyhow::Error> for RequestError {
+    fn from(err: anyhow::Error) -> Self {
+        Self::Internal(err)
+    }
+}
+
+impl RequestError {
+    fn into_response(self, id: serde_json::Value) -> JsonRpcResponse {
+        match self {
+            Self::InvalidParams(err) => {
+                JsonRpcResponse::error(id, INVALID_PARAMS, "Invalid params", Some(err.to_string()))
+            }
+            Self::Internal(err) => {
+                JsonRpcResponse::error(id, INTERNAL_ERROR, "Internal error", Some(format!("{err:#}")))
+            }
+        }
+    }
+}
+
+/// Deserialize the params of a request, treating missing params as an
+/// empty object
+fn parse_params<T: DeserializeOwned>(params: Option<serde_json::Value>) -> std::result::Result<T, RequestError> {
+    let params = params.unwrap_or_else(|| serde_json::json!({}));
+    serde_json::from_value(params).map_err(RequestError::InvalidParams)
+}
+
+/// `serverInfo` used when neither the route nor the application names one
+const DEFAULT_SERVER_NAME: &str = "spin-mcp-server";
+const DEFAULT_SERVER_VERSION: &str = "0.1.0";
+
+/// A single JSON-RPC message or a batch of them
+#[derive(Debug)]
+enum JsonRpcPayload {
+    Batch(Vec<JsonRpcEntry>),
+    Single(JsonRpcEntry),
+}
+
+/// One message of a payload
+#[derive(Debug)]
+enum JsonRpcEntry {
+    /// A valid request or notification
+    Request(JsonRpcRequest),
+    /// The client's reply to a request the server sent it
+    Response(JsonRpcResponse),
+    /// A message that can't be handled, with the error to answer it with
+    Invalid(JsonRpcResponse),
+}
+
+impl JsonRpcPayload {
+    /// Parse a message body. A body that isn't JSON at all is treated as a
+    /// single invalid message.
+    fn parse(bytes: &[u8]) -> Self {
+        match serde_json::from_slice(bytes) {
+            Ok(serde_json::Value::Array(entries)) => {
+                Self::Batch(entries.into_iter().map(JsonRpcEntry::parse).collect())
+            }
+            Ok(value) => Self::Single(JsonRpcEntry::parse(value)),
+            Err(err) => Self::Single(JsonRpcEntry::Invalid(JsonRpcResponse::error(
+                serde_json::Value::Null,
+                PARSE_ERROR,
+                "Parse error",
+                Some(err.to_string()),
+            ))),
+        }
+    }
+
+    /// Whether this is the `initialize` handshake
+    fn is_initialize(&self) -> bool {
+        matches!(self, Self::Single(JsonRpcEntry::Request(request)) if request.method == "initialize")
+    }
+
+    /// Whether the client expects a reply. Invalid messages and empty
+    /// batches are always answered with an error.
+    fn expects_response(&self) -> bool {
+        let expects = |entry: &JsonRpcEntry| match entry {
+            JsonRpcEntry::Request(request) => request.id.is_some(),
+            JsonRpcEntry::Response(_) => false,
+            JsonRpcEntry::Invalid(_) => true,
+        };
+        match self {
+            Self::Single(entry) => expects(entry),
+            Self::Batch(entries) => entries.is_empty() || entries.iter().any(expects),
+        }
+    }
+}
+
+impl JsonRpcEntry {
+    /// Validate a JSON value as a request, notification or response
+    fn parse(value: serde_json::Value) -> Self {
+        // Echo the id back if there is a usable one
+        let id = value
+            .get("id")
+            .filter(|id| id.is_string() || id.is_number())
+            .cloned()
+            .unwrap_or_default();
+
+        // Replies to requests the server sent carry a result or error instead of a method
+        let is_response =
+            value.get("method").is_none() && (value.get("result").is_some() || value.get("error").is_some());
+        if is_response {
+            return match serde_json::from_value::<JsonRpcResponse>(value) {
+                Ok(response) => Self::Response(response),
+                Err(err) => Self::Invalid(JsonRpcResponse::error(
+                    id,
+                    INVALID_REQUEST,
+                    "Invalid Request",
+                    Some(err.to_string()),
+                )),
+            };
+        }
+
+        match serde_json::from_value::<JsonRpcRequest>(value) {
+            Ok(request) if request.jsonrpc == "2.0" => Self::Request(request),
+            Ok(request) => Self::Invalid(JsonRpcResponse::error(
+                id,
+                INVALID_REQUEST,
+                "Invalid Request",
+                Some(format!("Unsupported JSON-RPC version: {}", request.jsonrpc)),
+            )),
+            Err(err) => Self::Invalid(JsonRpcResponse::error(
+                id,
+                INVALID_REQUEST,
+                "Invalid Request",
+                Some(err.to_string()),
+            )),
+        }
+    }
 }
 
 /// JSON-RPC request structure
 #[derive(Debug, Deserialize)]
 struct JsonRpcRequest {
-    #[allow(dead_code)]
     jsonrpc: String,
     method: String,
     params: Option<serde_json::Value>,
@@ -515,7 +1415,7 @@ struct JsonRpcRequest {
 }
 
 /// JSON-RPC response structure
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 struct JsonRpcResponse {
     jsonrpc: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -525,7 +1425,7 @@ struct JsonRpcResponse {
     id: serde_json::Value,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 struct JsonRpcError {
     code: i32,
     message: String,
@@ -558,10 +1458,47 @@ impl JsonRpcResponse {
 }
 
 // Parameter structures for JSON-RPC methods
+#[derive(Debug, Default, Deserialize)]
+struct InitializeParams {
+    #[serde(default, rename = "protocolVersion")]
+    protocol_version: Option<String>,
+    #[serde(default)]
+    capabilities: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListParams {
+    #[serde(default)]
+    cursor: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 struct ToolsCallParams {
     name: String,
+    #[serde(default = "empty_arguments")]
     arguments: serde_json::Value,
+    #[serde(default, rename = "_meta")]
+    meta: RequestMeta,
+}
+
+/// The `_meta` object of request params
+#[derive(Debug, Default, Deserialize)]
+struct RequestMeta {
+    #[serde(rename = "progressToken")]
+    progress_token: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CancelledParams {
+    #[serde(rename = "requestId")]
+    request_id: serde_json::Value,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SetLevelParams {
+    level: LogLevel,
 }
 
 #[derive(Debug, Deserialize)]
@@ -572,5 +1509,139 @@ struct ResourceReadParams {
 #[derive(Debug, Deserialize)]
 struct PromptGetParams {
     name: String,
+    #[serde(default = "empty_arguments")]
     arguments: serde_json::Value,
-}
\ No newline at end of file
+}
+
+/// `arguments` is optional in MCP, components always receive an object
+fn empty_arguments() -> serde_json::Value {
+    serde_json::json!({})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(body: serde_json::Value) -> JsonRpcPayload {
+        JsonRpcPayload::parse(body.to_string().as_bytes())
+    }
+
+    #[test]
+    fn only_requests_expect_responses() {
+        assert!(payload(serde_json::json!({ "jsonrpc": "2.0", "id": 1, "method": "ping" })).expects_response());
+        let notification = serde_json::json!({ "jsonrpc": "2.0", "method": "notifications/initialized" });
+        assert!(!payload(notification).expects_response());
+    }
+
+    #[test]
+    fn batches_expect_responses_if_any_entry_does() {
+        assert!(payload(serde_json::json!([])).expects_response());
+        assert!(payload(serde_json::json!([
+            { "jsonrpc": "2.0", "method": "notifications/initialized" },
+            { "jsonrpc": "2.0", "id": 1, "method": "ping" },
+        ]))
+        .expects_response());
+        assert!(!payload(serde_json::json!([
+            { "jsonrpc": "2.0", "method": "notifications/initialized" },
+            { "jsonrpc": "2.0", "method": "notifications/roots/list_changed" },
+        ]))
+        .expects_response());
+    }
+
+    #[test]
+    fn list_page_size_must_not_be_zero() {
+        let config = |size: usize| {
+            serde_json::from_value::<ComponentConfig>(serde_json::json!({
+                "component": "tools",
+                "route": "/mcp",
+                "list_page_size": size,
+            }))
+        };
+        assert!(config(0).is_err());
+        assert_eq!(config(10).unwrap().list_page_size, NonZeroUsize::new(10));
+    }
+
+    fn entry(value: serde_json::Value) -> JsonRpcEntry {
+        JsonRpcEntry::parse(value)
+    }
+
+    fn error_code(entry: &JsonRpcEntry) -> Option<i32> {
+        match entry {
+            JsonRpcEntry::Invalid(response) => response.error.as_ref().map(|error| error.code),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn parse_accepts_requests_and_notifications() {
+        let request = entry(serde_json::json!({ "jsonrpc": "2.0", "id": 1, "method": "ping" }));
+        assert!(matches!(request, JsonRpcEntry::Request(ref request) if request.method == "ping"));
+
+        let notification = entry(serde_json::json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }));
+        assert!(matches!(notification, JsonRpcEntry::Request(ref request) if request.id.is_none()));
+    }
+
+    #[test]
+    fn parse_rejects_invalid_messages() {
+        let wrong_version = entry(serde_json::json!({ "jsonrpc": "1.0", "id": 1, "method": "ping" }));
+        assert_eq!(error_code(&wrong_version), Some(INVALID_REQUEST));
+
+        let no_method = entry(serde_json::json!({ "jsonrpc": "2.0", "id": 1 }));
+        assert_eq!(error_code(&no_method), Some(INVALID_REQUEST));
+
+        let not_an_object = entry(serde_json::json!(42));
+        assert_eq!(error_code(&not_an_object), Some(INVALID_REQUEST));
+    }
+
+    #[test]
+    fn parse_echoes_usable_ids_only() {
+        let JsonRpcEntry::Invalid(response) = entry(serde_json::json!({ "jsonrpc": "1.0", "id": "a", "method": "x" }))
+        else {
+            panic!("expected an invalid entry");
+        };
+        assert_eq!(response.id, serde_json::json!("a"));
+
+        let JsonRpcEntry::Invalid(response) = entry(serde_json::json!({ "jsonrpc": "1.0", "id": {}, "method": "x" }))
+        else {
+            panic!("expected an invalid entry");
+        };
+        assert_eq!(response.id, serde_json::Value::Null);
+    }
+
+    #[test]
+    fn body_that_is_not_json_is_a_parse_error() {
+        let JsonRpcPayload::Single(entry) = JsonRpcPayload::parse(b"{") else {
+            panic!("expected a single entry");
+        };
+        assert_eq!(error_code(&entry), Some(PARSE_ERROR));
+    }
+
+    #[test]
+    fn invalid_messages_expect_responses() {
+        assert!(payload(serde_json::json!({ "jsonrpc": "2.0", "id": 1 })).expects_response());
+        assert!(payload(serde_json::json!([{ "jsonrpc": "2.0", "method": 7 }])).expects_response());
+    }
+
+    #[test]
+    fn parse_recognizes_client_responses() {
+        let result = entry(serde_json::json!({ "jsonrpc": "2.0", "id": 3, "result": {} }));
+        assert!(matches!(result, JsonRpcEntry::Response(_)));
+
+        let error = entry(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 4,
+            "error": { "code": -1, "message": "User rejected" },
+        }));
+        assert!(matches!(error, JsonRpcEntry::Response(ref response) if response.error.is_some()));
+    }
+
+    #[test]
+    fn client_responses_do_not_expect_responses() {
+        assert!(!payload(serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": {} })).expects_response());
+        assert!(!payload(serde_json::json!([
+            { "jsonrpc": "2.0", "method": "notifications/initialized" },
+            { "jsonrpc": "2.0", "id": 1, "result": {} },
+        ]))
+        .expects_response());
+    }
+}
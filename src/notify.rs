@@ -0,0 +1,107 @@
+//! Server-to-client notifications raised by components through the host
+//! functions they import.
+
+use crate::cache::ListCache;
+use crate::route::{Kind, McpRoute, RouteOwners};
+use crate::session::{Session, SessionStore};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tracing::debug;
+
+/// Delivers notifications from components to the sessions they concern
+pub(crate) struct Notifier {
+    sessions: Arc<SessionStore>,
+    list_cache: Arc<ListCache>,
+    /// Owners of entries on aggregated routes, by route
+    owners: Arc<Mutex<HashMap<String, RouteOwners>>>,
+    /// Routes served by each component
+    routes: HashMap<String, Vec<String>>,
+}
+
+impl Notifier {
+    pub(crate) fn new(
+        sessions: Arc<SessionStore>,
+        list_cache: Arc<ListCache>,
+        owners: Arc<Mutex<HashMap<String, RouteOwners>>>,
+        routes: &HashMap<String, Arc<McpRoute>>,
+    ) -> Self {
+        let mut by_component: HashMap<String, Vec<String>> = HashMap::new();
+        for route in routes.values() {
+            for component in &route.components {
+                by_component
+                    .entry(component.id.clone())
+                    .or_default()
+                    .push(route.path.clone());
+            }
+        }
+        Self {
+            sessions,
+            list_cache,
+            owners,
+            routes: by_component,
+        }
+    }
+
+    /// Tell sessions subscribed to a component's resource that it changed
+    pub(crate) fn resource_updated(&self, component_id: &str, uri: &str) {
+        let message = notification("notifications/resources/updated", serde_json::json!({ "uri": uri }));
+        for session in self.sessions_of(component_id) {
+            if session.is_subscribed(uri) && !session.send(message.clone()) {
+                debug!("MCP session {} has no open stream for resource update of {}", session.id(), uri);
+            }
+        }
+    }
+
+    /// Tell every session on the component's routes that a list changed,
+    /// dropping cached copies of it and the owners learned from them first
+    /// so clients and routing see fresh results
+    pub(crate) fn list_changed(&self, component_id: &str, kind: Kind) {
+        let (method, kinds): (_, &[Kind]) = match kind {
+            Kind::Tool => ("notifications/tools/list_changed", &[Kind::Tool]),
+            Kind::Prompt => ("notifications/prompts/list_changed", &[Kind::Prompt]),
+            Kind::Resource | Kind::ResourceTemplate => (
+                "notifications/resources/list_changed",
+                &[Kind::Resource, Kind::ResourceTemplate],
+            ),
+        };
+
+        for route in self.routes.get(component_id).into_iter().flatten() {
+            for kind in kinds {
+                self.list_cache.invalidate(route, *kind);
+            }
+            if let Some(owners) = self.owners.lock().unwrap().get_mut(route) {
+                for kind in kinds {
+                    owners.forget(*kind);
+                }
+            }
+        }
+
+        let message = notification(method, serde_json::json!({}));
+        for session in self.sessions_of(component_id) {
+            if !session.send(message.clone()) {
+                debug!("MCP session {} has no open stream for {}", session.id(), method);
+            }
+        }
+    }
+
+    /// Live sessions on the routes a component serves
+    fn sessions_of(&self, component_id: &str) -> Vec<Arc<Session>> {
+        let Some(routes) = self.routes.get(component_id) else {
+            return Vec::new();
+        };
+        self.sessions
+            .all()
+            .into_iter()
+            .filter(|session| routes.iter().any(|route| route == session.route()))
+            .collect()
+    }
+}
+
+/// A JSON-RPC notification message
+pub(crate) fn notification(method: &str, params: serde_json::Value) -> serde_json::Value {
+    serde_json::json!({
+        "jsonrpc": "2.0",
+        "method": method,
+        "params": params,
+    })
+}
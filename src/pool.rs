@@ -0,0 +1,100 @@
+//! Warm pools of pre-instantiated components, so requests don't pay for
+//! instantiation.
+
+use crate::host::McpInstanceState;
+use crate::notify::Notifier;
+use crate::{McpTrigger, SpinMcp};
+use anyhow::Result;
+use spin_factors::RuntimeFactors;
+use spin_trigger::TriggerApp;
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+/// A component instance and its bindings, ready to handle one request
+pub(crate) struct McpInstance<F: RuntimeFactors> {
+    pub(crate) bindings: SpinMcp,
+    pub(crate) store: spin_trigger::Store<McpTrigger, F>,
+}
+
+/// Prepare and instantiate a component. Spin keeps the component's
+/// `InstancePre` around, so this only builds the store and instance.
+pub(crate) async fn instantiate<F: RuntimeFactors>(
+    trigger_app: &TriggerApp<McpTrigger, F>,
+    component_id: &str,
+    notifier: &Arc<Notifier>,
+) -> Result<McpInstance<F>> {
+    let instance_builder = trigger_app.prepare(component_id)?;
+    let state = McpInstanceState::new(component_id, notifier.clone());
+    let (instance, mut store) = instance_builder.instantiate(state).await?;
+    let bindings = SpinMcp::new(&mut store, &instance)?;
+    Ok(McpInstance { bindings, store })
+}
+
+/// Instances of one component created ahead of time. Each instance serves a
+/// single request and is replaced in the background once taken.
+pub(crate) struct InstancePool<F: RuntimeFactors> {
+    component_id: String,
+    size: usize,
+    trigger_app: Arc<TriggerApp<McpTrigger, F>>,
+    notifier: Arc<Notifier>,
+    state: Mutex<PoolState<F>>,
+}
+
+struct PoolState<F: RuntimeFactors> {
+    ready: Vec<McpInstance<F>>,
+    /// Instances currently being created
+    pending: usize,
+}
+
+impl<F: RuntimeFactors> InstancePool<F> {
+    pub(crate) fn new(
+        component_id: &str,
+        size: usize,
+        trigger_app: Arc<TriggerApp<McpTrigger, F>>,
+        notifier: Arc<Notifier>,
+    ) -> Self {
+        Self {
+            component_id: component_id.to_string(),
+            size,
+            trigger_app,
+            notifier,
+            state: Mutex::new(PoolState {
+                ready: Vec::new(),
+                pending: 0,
+            }),
+        }
+    }
+
+    /// Take a ready instance, if there is one, and start replacing it
+    pub(crate) fn take(self: &Arc<Self>) -> Option<McpInstance<F>> {
+        let instance = self.state.lock().unwrap().ready.pop();
+        self.refill();
+        instance
+    }
+
+    /// Create instances in the background until the pool is full
+    pub(crate) fn refill(self: &Arc<Self>) {
+        let missing = {
+            let mut state = self.state.lock().unwrap();
+            let missing = self.size.saturating_sub(state.ready.len() + state.pending);
+            state.pending += missing;
+            missing
+        };
+
+        for _ in 0..missing {
+            let pool = self.clone();
+            tokio::spawn(async move {
+                let instance = instantiate(&pool.trigger_app, &pool.component_id, &pool.notifier).await;
+                let mut state = pool.state.lock().unwrap();
+                state.pending -= 1;
+                match instance {
+                    Ok(instance) => state.ready.push(instance),
+                    Err(err) => warn!(
+                        "Failed to pre-instantiate MCP component {}: {err:?}",
+                        pool.component_id
+                    ),
+                }
+            });
+        }
+    }
+}
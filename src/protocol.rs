@@ -0,0 +1,97 @@
+//! MCP protocol versions and their negotiation during `initialize`.
+
+use std::fmt;
+
+/// A protocol version this trigger can speak. Versions are ordered from
+/// oldest to newest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum ProtocolVersion {
+    V2024_11_05,
+    V2025_03_26,
+    V2025_06_18,
+}
+
+impl ProtocolVersion {
+    /// Every supported version
+    pub(crate) const SUPPORTED: [Self; 3] = [Self::V2024_11_05, Self::V2025_03_26, Self::V2025_06_18];
+
+    /// Offered to clients asking for a version we don't support
+    pub(crate) const LATEST: Self = Self::V2025_06_18;
+
+    /// Assumed for requests that don't say which version they use, as the
+    /// spec requires for HTTP requests without the version header
+    pub(crate) const DEFAULT: Self = Self::V2025_03_26;
+
+    pub(crate) fn parse(version: &str) -> Option<Self> {
+        Self::SUPPORTED.into_iter().find(|supported| supported.as_str() == version)
+    }
+
+    /// Pick the version for a session: the client's when we support it,
+    /// otherwise the newest we have
+    pub(crate) fn negotiate(requested: Option<&str>) -> Self {
+        requested.and_then(Self::parse).unwrap_or(Self::LATEST)
+    }
+
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::V2024_11_05 => "2024-11-05",
+            Self::V2025_03_26 => "2025-03-26",
+            Self::V2025_06_18 => "2025-06-18",
+        }
+    }
+
+    /// Whether progress notifications can carry a `message`
+    pub(crate) fn has_progress_messages(self) -> bool {
+        self >= Self::V2025_03_26
+    }
+
+    /// Whether tool results can contain `audio` content
+    pub(crate) fn has_audio(self) -> bool {
+        self >= Self::V2025_03_26
+    }
+
+    /// Whether tool results can contain `resource_link` content
+    pub(crate) fn has_resource_links(self) -> bool {
+        self >= Self::V2025_06_18
+    }
+
+    /// Whether tools can carry behavior `annotations`
+    pub(crate) fn has_tool_annotations(self) -> bool {
+        self >= Self::V2025_03_26
+    }
+
+    /// Whether server info, tools, resources and prompts can carry a
+    /// display `title`
+    pub(crate) fn has_titles(self) -> bool {
+        self >= Self::V2025_06_18
+    }
+
+    /// Whether tool results can carry `structuredContent`
+    pub(crate) fn has_structured_content(self) -> bool {
+        self >= Self::V2025_06_18
+    }
+}
+
+impl fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negotiate_keeps_supported_versions() {
+        for version in ProtocolVersion::SUPPORTED {
+            assert_eq!(ProtocolVersion::negotiate(Some(version.as_str())), version);
+        }
+    }
+
+    #[test]
+    fn negotiate_offers_latest_otherwise() {
+        assert_eq!(ProtocolVersion::negotiate(Some("2099-01-01")), ProtocolVersion::LATEST);
+        assert_eq!(ProtocolVersion::negotiate(None), ProtocolVersion::LATEST);
+    }
+}
@@ -0,0 +1,715 @@
+//! MCP routes and the components behind them. Several components can share
+//! a route, in which case they are served as one logical MCP server: list
+//! results are merged and calls are sent to the component that owns the
+//! tool, prompt or resource.
+
+use crate::cache::ListCacheMode;
+use crate::host::RequestContext;
+use crate::{mcp, McpServer};
+use anyhow::Result;
+use base64::Engine;
+use spin_factors::RuntimeFactors;
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroUsize;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// A route and the components serving it
+#[derive(Debug)]
+pub(crate) struct McpRoute {
+    pub(crate) path: String,
+    pub(crate) components: Vec<RouteComponent>,
+    /// How long list results are cached, if at all
+    pub(crate) list_cache: Option<ListCacheMode>,
+    /// Number of entries per page when the trigger paginates lists itself
+    pub(crate) list_page_size: Option<NonZeroUsize>,
+    /// How the route introduces itself to clients
+    pub(crate) server_info: ServerInfo,
+}
+
+/// The `serverInfo` and `instructions` of an `initialize` result. Unset
+/// fields fall back to the application's.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ServerInfo {
+    pub(crate) name: Option<String>,
+    pub(crate) title: Option<String>,
+    pub(crate) version: Option<String>,
+    pub(crate) instructions: Option<String>,
+}
+
+/// A component serving (part of) a route
+#[derive(Clone, Debug)]
+pub(crate) struct RouteComponent {
+    pub(crate) id: String,
+    /// Prepended to the names of the component's tools and prompts
+    pub(crate) prefix: Option<String>,
+}
+
+impl McpRoute {
+    /// Whether a request can go to a single component unchanged. Lists are
+    /// not passed through when the trigger paginates them itself.
+    fn is_passthrough(&self, request: &mcp::Request) -> bool {
+        let paginated = self.list_page_size.is_some() && Kind::of_list(request).is_some();
+        !paginated && matches!(self.components.as_slice(), [component] if component.prefix.is_none())
+    }
+}
+
+impl RouteComponent {
+    /// The name a client sees for one of the component's tools or prompts
+    fn public_name(&self, name: &str) -> String {
+        match &self.prefix {
+            Some(prefix) => format!("{prefix}{name}"),
+            None => name.to_string(),
+        }
+    }
+
+    /// The component's own name for a client-facing name
+    fn local_name(&self, name: &str) -> String {
+        self.prefix
+            .as_deref()
+            .and_then(|prefix| name.strip_prefix(prefix))
+            .unwrap_or(name)
+            .to_string()
+    }
+}
+
+/// What kind of entry an owner is tracked or a list is cached for
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Kind {
+    Tool,
+    Prompt,
+    Resource,
+    ResourceTemplate,
+}
+
+impl Kind {
+    /// The kind of entry a request lists, if it is a list request
+    fn of_list(request: &mcp::Request) -> Option<Self> {
+        match request {
+            mcp::Request::ToolsList(_) => Some(Self::Tool),
+            mcp::Request::PromptsList(_) => Some(Self::Prompt),
+            mcp::Request::ResourcesList(_) => Some(Self::Resource),
+            mcp::Request::ResourcesTemplatesList(_) => Some(Self::ResourceTemplate),
+            _ => None,
+        }
+    }
+
+    /// The kind of entry a request lists, if it asks for the first page of
+    /// a list
+    fn of_first_page(request: &mcp::Request) -> Option<Self> {
+        match request {
+            mcp::Request::ToolsList(mcp::ListParams { cursor: None })
+            | mcp::Request::PromptsList(mcp::ListParams { cursor: None })
+            | mcp::Request::ResourcesList(mcp::ListParams { cursor: None })
+            | mcp::Request::ResourcesTemplatesList(mcp::ListParams { cursor: None }) => Self::of_list(request),
+            _ => None,
+        }
+    }
+}
+
+/// An entry returned by one of the list methods
+trait Listed: Sized + Clone {
+    const KIND: Kind;
+    /// Whether the entry's name gets the component's prefix
+    const PREFIXED: bool;
+
+    fn request(cursor: Option<String>) -> mcp::Request;
+    /// The entries and next cursor of a list response, or the response
+    /// itself if it isn't a page of this kind
+    fn page(response: mcp::Response) -> std::result::Result<(Vec<Self>, Option<String>), mcp::Response>;
+    fn response(entries: Vec<Self>, next_cursor: Option<String>) -> mcp::Response;
+    /// The name or URI the entry is addressed by
+    fn key(&mut self) -> &mut String;
+}
+
+impl Listed for mcp::Tool {
+    const KIND: Kind = Kind::Tool;
+    const PREFIXED: bool = true;
+
+    fn request(cursor: Option<String>) -> mcp::Request {
+        mcp::Request::ToolsList(mcp::ListParams { cursor })
+    }
+
+    fn page(response: mcp::Response) -> std::result::Result<(Vec<Self>, Option<String>), mcp::Response> {
+        match response {
+            mcp::Response::ToolsList(page) => Ok((page.tools, page.next_cursor)),
+            other => Err(other),
+        }
+    }
+
+    fn response(tools: Vec<Self>, next_cursor: Option<String>) -> mcp::Response {
+        mcp::Response::ToolsList(mcp::ToolsPage { tools, next_cursor })
+    }
+
+    fn key(&mut self) -> &mut String {
+        &mut self.name
+    }
+}
+
+impl Listed for mcp::Prompt {
+    const KIND: Kind = Kind::Prompt;
+    const PREFIXED: bool = true;
+
+    fn request(cursor: Option<String>) -> mcp::Request {
+        mcp::Request::PromptsList(mcp::ListParams { cursor })
+    }
+
+    fn page(response: mcp::Response) -> std::result::Result<(Vec<Self>, Option<String>), mcp::Response> {
+        match response {
+            mcp::Response::PromptsList(page) => Ok((page.prompts, page.next_cursor)),
+            other => Err(other),
+        }
+    }
+
+    fn response(prompts: Vec<Self>, next_cursor: Option<String>) -> mcp::Response {
+        mcp::Response::PromptsList(mcp::PromptsPage { prompts, next_cursor })
+    }
+
+    fn key(&mut self) -> &mut String {
+        &mut self.name
+    }
+}
+
+impl Listed for mcp::ResourceInfo {
+    const KIND: Kind = Kind::Resource;
+    // Resources are addressed by URI, which is never prefixed
+    const PREFIXED: bool = false;
+
+    fn request(cursor: Option<String>) -> mcp::Request {
+        mcp::Request::ResourcesList(mcp::ListParams { cursor })
+    }
+
+    fn page(response: mcp::Response) -> std::result::Result<(Vec<Self>, Option<String>), mcp::Response> {
+        match response {
+            mcp::Response::ResourcesList(page) => Ok((page.resources, page.next_cursor)),
+            other => Err(other),
+        }
+    }
+
+    fn response(resources: Vec<Self>, next_cursor: Option<String>) -> mcp::Response {
+        mcp::Response::ResourcesList(mcp::ResourcesPage { resources, next_cursor })
+    }
+
+    fn key(&mut self) -> &mut String {
+        &mut self.uri
+    }
+}
+
+impl Listed for mcp::ResourceTemplate {
+    const KIND: Kind = Kind::ResourceTemplate;
+    const PREFIXED: bool = false;
+
+    fn request(cursor: Option<String>) -> mcp::Request {
+        mcp::Request::ResourcesTemplatesList(mcp::ListParams { cursor })
+    }
+
+    fn page(response: mcp::Response) -> std::result::Result<(Vec<Self>, Option<String>), mcp::Response> {
+        match response {
+            mcp::Response::ResourcesTemplatesList(page) => Ok((page.resource_templates, page.next_cursor)),
+            other => Err(other),
+        }
+    }
+
+    fn response(resource_templates: Vec<Self>, next_cursor: Option<String>) -> mcp::Response {
+        mcp::Response::ResourcesTemplatesList(mcp::ResourceTemplatesPage {
+            resource_templates,
+            next_cursor,
+        })
+    }
+
+    fn key(&mut self) -> &mut String {
+        &mut self.uri_template
+    }
+}
+
+/// Most pages the trigger will fetch from one component for a single list
+const MAX_COMPONENT_PAGES: usize = 1000;
+
+/// How long later pages of a paginated route are served from the list
+/// merged for its first page, on routes without a `list_cache`
+const MERGED_LIST_TTL: Duration = Duration::from_secs(60);
+
+/// Which component on an aggregated route owns each client-facing tool,
+/// prompt and resource, as learned from the last list results
+#[derive(Default)]
+pub(crate) struct RouteOwners {
+    tools: HashMap<String, usize>,
+    prompts: HashMap<String, usize>,
+    resources: HashMap<String, usize>,
+    resource_templates: HashMap<String, usize>,
+    /// Kinds the route has been listed for at least once
+    listed: HashSet<Kind>,
+}
+
+impl RouteOwners {
+    fn of(&mut self, kind: Kind) -> &mut HashMap<String, usize> {
+        match kind {
+            Kind::Tool => &mut self.tools,
+            Kind::Prompt => &mut self.prompts,
+            Kind::Resource => &mut self.resources,
+            Kind::ResourceTemplate => &mut self.resource_templates,
+        }
+    }
+
+    /// Replace the owners of a kind with those of a fresh list
+    fn record(&mut self, kind: Kind, owners: HashMap<String, usize>) {
+        *self.of(kind) = owners;
+        self.listed.insert(kind);
+    }
+
+    /// Forget the owners of a kind, so the route is listed again the next
+    /// time one is needed
+    pub(crate) fn forget(&mut self, kind: Kind) {
+        self.of(kind).clear();
+        self.listed.remove(&kind);
+    }
+
+    /// The owner of a name. Resource templates own every URI they match,
+    /// with the earliest component winning if several match.
+    fn find(&mut self, kind: Kind, name: &str) -> Option<usize> {
+        match kind {
+            Kind::ResourceTemplate => self
+                .resource_templates
+                .iter()
+                .filter(|(template, _)| matches_template(template, name))
+                .map(|(_, index)| *index)
+                .min(),
+            _ => self.of(kind).get(name).copied(),
+        }
+    }
+}
+
+impl<F: RuntimeFactors> McpServer<F> {
+    /// Send a request to the component(s) serving a route, answering list
+    /// requests from the cache when the route has one
+    pub(crate) async fn call_route(
+        &self,
+        route: &McpRoute,
+        request: mcp::Request,
+        context: &RequestContext,
+    ) -> Result<mcp::Response> {
+        let (Some(mode), Some(kind)) = (route.list_cache, Kind::of_first_page(&request)) else {
+            return self.route_request(route, request, context).await;
+        };
+
+        if let Some(response) = self.list_cache.get(&route.path, kind) {
+            return Ok(response);
+        }
+        let response = self.route_request(route, request, context).await?;
+        if !matches!(response, mcp::Response::Error(_)) {
+            self.list_cache.insert(&route.path, kind, mode, response.clone());
+        }
+        Ok(response)
+    }
+
+    async fn route_request(
+        &self,
+        route: &McpRoute,
+        request: mcp::Request,
+        context: &RequestContext,
+    ) -> Result<mcp::Response> {
+        if route.is_passthrough(&request) {
+            return self.call_component(&route.components[0].id, &request, context).await;
+        }
+
+        match request {
+            mcp::Request::ToolsList(params) => self.list::<mcp::Tool>(route, params.cursor, context).await,
+            mcp::Request::PromptsList(params) => self.list::<mcp::Prompt>(route, params.cursor, context).await,
+            mcp::Request::ResourcesList(params) => self.list::<mcp::ResourceInfo>(route, params.cursor, context).await,
+            mcp::Request::ResourcesTemplatesList(params) => {
+                self.list::<mcp::ResourceTemplate>(route, params.cursor, context).await
+            }
+            mcp::Request::ToolsCall(params) => {
+                let Some(component) = self.owner(route, Kind::Tool, &params.name, context).await? else {
+                    return Ok(not_found("tool", &params.name));
+                };
+                let request = mcp::Request::ToolsCall(mcp::ToolsCallParams {
+                    name: component.local_name(&params.name),
+                    arguments: params.arguments,
+                    progress_token: params.progress_token,
+                });
+                self.call_component(&component.id, &request, context).await
+            }
+            mcp::Request::PromptsGet(params) => {
+                let Some(component) = self.owner(route, Kind::Prompt, &params.name, context).await? else {
+                    return Ok(not_found("prompt", &params.name));
+                };
+                let request = mcp::Request::PromptsGet(mcp::PromptGetParams {
+                    name: component.local_name(&params.name),
+                    arguments: params.arguments,
+                });
+                self.call_component(&component.id, &request, context).await
+            }
+            mcp::Request::ResourcesRead(mcp::ResourceReadParams { ref uri })
+            | mcp::Request::ResourcesSubscribe(mcp::ResourceSubscribeParams { ref uri })
+            | mcp::Request::ResourcesUnsubscribe(mcp::ResourceUnsubscribeParams { ref uri }) => {
+                // Templated URIs are never listed, so try templates before
+                // listing every resource of the route
+                let owner = match self.recorded_owner(route, Kind::Resource, uri).0 {
+                    Some(component) => Some(component),
+                    None => match self.owner(route, Kind::ResourceTemplate, uri, context).await? {
+                        Some(component) => Some(component),
+                        None => self.owner(route, Kind::Resource, uri, context).await?,
+                    },
+                };
+                if let Some(component) = owner {
+                    return self.call_component(&component.id, &request, context).await;
+                }
+                // Not a listed or templated resource, ask each component in turn
+                let mut response = not_found("resource", uri);
+                for component in &route.components {
+                    response = self.call_component(&component.id, &request, context).await?;
+                    if !matches!(response, mcp::Response::Error(_)) {
+                        break;
+                    }
+                }
+                Ok(response)
+            }
+            mcp::Request::Ping => {
+                // The route is healthy only if every component is
+                let responses = self.fan_out(route, &request, context).await?;
+                Ok(responses
+                    .into_iter()
+                    .find(|response| matches!(response, mcp::Response::Error(_)))
+                    .unwrap_or(mcp::Response::Pong))
+            }
+        }
+    }
+
+    /// Send the same request to every component on a route concurrently
+    async fn fan_out(
+        &self,
+        route: &McpRoute,
+        request: &mcp::Request,
+        context: &RequestContext,
+    ) -> Result<Vec<mcp::Response>> {
+        futures::future::try_join_all(
+            route
+                .components
+                .iter()
+                .map(|component| self.call_component(&component.id, request, context)),
+        )
+        .await
+    }
+
+    /// List tools, prompts or resources of every component on a route,
+    /// recording which component owns each entry. Routes with a page size
+    /// get one page of the merged list at a time; later pages come from the
+    /// list merged for the first one, so walking the cursors doesn't list
+    /// every component again for each page.
+    async fn list<T: Listed>(
+        &self,
+        route: &McpRoute,
+        cursor: Option<String>,
+        context: &RequestContext,
+    ) -> Result<mcp::Response> {
+        let offset = match cursor {
+            None => None,
+            // Cursors are only handed out when the trigger paginates
+            Some(cursor) => match route.list_page_size.and_then(|_| decode_cursor(&cursor)) {
+                Some(offset) => Some(offset),
+                None => return Ok(invalid_cursor(&cursor)),
+            },
+        };
+
+        let merged = offset
+            .and_then(|_| self.list_cache.get_merged(&route.path, T::KIND))
+            .and_then(|response| T::page(response).ok());
+        let entries = match merged {
+            Some((entries, _)) => entries,
+            None => self.list_all::<T>(route, context).await?,
+        };
+
+        let Some(page_size) = route.list_page_size.map(NonZeroUsize::get) else {
+            return Ok(T::response(entries, None));
+        };
+        let offset = offset.unwrap_or(0);
+        let next_offset = offset.saturating_add(page_size);
+        let next_cursor = (next_offset < entries.len()).then(|| encode_cursor(next_offset));
+        let page = entries.into_iter().skip(offset).take(page_size).collect();
+        Ok(T::response(page, next_cursor))
+    }
+
+    /// List and merge the entries of every component on a route, keeping
+    /// the merged list for later pages when the route is paginated
+    async fn list_all<T: Listed>(&self, route: &McpRoute, context: &RequestContext) -> Result<Vec<T>> {
+        let lists = futures::future::try_join_all(
+            route
+                .components
+                .iter()
+                .map(|component| self.list_component::<T>(&component.id, context)),
+        )
+        .await?;
+        let (entries, owners) = merge(route, T::KIND, lists.into_iter(), T::key, T::PREFIXED);
+
+        self.owners
+            .lock()
+            .unwrap()
+            .entry(route.path.clone())
+            .or_default()
+            .record(T::KIND, owners);
+
+        if route.list_page_size.is_some() {
+            let mode = route.list_cache.unwrap_or(ListCacheMode::Ttl(MERGED_LIST_TTL));
+            self.list_cache
+                .insert_merged(&route.path, T::KIND, mode, T::response(entries.clone(), None));
+        }
+        Ok(entries)
+    }
+
+    /// List every entry of a component, following its cursors
+    async fn list_component<T: Listed>(&self, component_id: &str, context: &RequestContext) -> Result<Vec<T>> {
+        let mut entries = Vec::new();
+        let mut cursor = None;
+        for _ in 0..MAX_COMPONENT_PAGES {
+            let response = self.call_component(component_id, &T::request(cursor), context).await?;
+            match T::page(response) {
+                Ok((page, next_cursor)) => {
+                    entries.extend(page);
+                    cursor = next_cursor;
+                }
+                Err(response) => {
+                    unlisted(T::KIND, response);
+                    cursor = None;
+                }
+            }
+            if cursor.is_none() {
+                return Ok(entries);
+            }
+        }
+        warn!(
+            "Component {} returned more than {} pages of {:?} entries, ignoring the rest",
+            component_id, MAX_COMPONENT_PAGES, T::KIND
+        );
+        Ok(entries)
+    }
+
+    /// Find the component that owns a client-facing name, listing the route
+    /// first if it hasn't been listed for that kind yet, or since a
+    /// `list_changed` notification for it.
+    async fn owner<'a>(
+        &self,
+        route: &'a McpRoute,
+        kind: Kind,
+        name: &str,
+        context: &RequestContext,
+    ) -> Result<Option<&'a RouteComponent>> {
+        let (owner, listed) = self.recorded_owner(route, kind, name);
+        if owner.is_some() || listed {
+            return Ok(owner);
+        }
+        match kind {
+            Kind::Tool => self.list::<mcp::Tool>(route, None, context).await?,
+            Kind::Prompt => self.list::<mcp::Prompt>(route, None, context).await?,
+            Kind::Resource => self.list::<mcp::ResourceInfo>(route, None, context).await?,
+            Kind::ResourceTemplate => self.list::<mcp::ResourceTemplate>(route, None, context).await?,
+        };
+        Ok(self.recorded_owner(route, kind, name).0)
+    }
+
+    /// The owner of a name as of the route's last list, and whether the
+    /// route was listed for that kind at all
+    fn recorded_owner<'a>(&self, route: &'a McpRoute, kind: Kind, name: &str) -> (Option<&'a RouteComponent>, bool) {
+        let mut owners = self.owners.lock().unwrap();
+        let Some(owners) = owners.get_mut(&route.path) else {
+            return (None, false);
+        };
+        let owner = owners.find(kind, name).and_then(|index| route.components.get(index));
+        (owner, owners.listed.contains(&kind))
+    }
+}
+
+/// Merge the lists returned by each component of a route. The first
+/// component to claim a name wins.
+fn merge<T>(
+    route: &McpRoute,
+    kind: Kind,
+    lists: impl Iterator<Item = Vec<T>>,
+    key: fn(&mut T) -> &mut String,
+    prefixed: bool,
+) -> (Vec<T>, HashMap<String, usize>) {
+    let mut merged = Vec::new();
+    let mut owners = HashMap::new();
+
+    for (index, list) in lists.enumerate() {
+        let component = &route.components[index];
+        for mut entry in list {
+            let name = key(&mut entry);
+            if prefixed {
+                *name = component.public_name(name);
+            }
+            if let Some(owner) = owners.get(name.as_str()) {
+                let owner: &RouteComponent = &route.components[*owner];
+                warn!(
+                    "{:?} {} on route {} is provided by both {} and {}, using {}. Set a prefix to expose both.",
+                    kind, name, route.path, owner.id, component.id, owner.id
+                );
+                continue;
+            }
+            owners.insert(name.clone(), index);
+            merged.push(entry);
+        }
+    }
+
+    (merged, owners)
+}
+
+/// A component that can't list a kind of entry contributes nothing
+fn unlisted(kind: Kind, response: mcp::Response) {
+    if let mcp::Response::Error(err) = response {
+        debug!("Component does not list {:?} entries: {}", kind, err.message);
+    }
+}
+
+/// Whether a URI could be an expansion of an RFC 6570 URI template. Each
+/// `{...}` expression matches any non-empty text, or any text at all for
+/// query expressions like `{?page}`.
+fn matches_template(template: &str, uri: &str) -> bool {
+    let Some(start) = template.find('{') else {
+        return template == uri;
+    };
+    let (literal, expression) = template.split_at(start);
+    let Some(uri) = uri.strip_prefix(literal) else {
+        return false;
+    };
+    let Some(end) = expression.find('}') else {
+        return false;
+    };
+    let optional = expression[1..].starts_with(['?', '&']);
+    let rest = &expression[end + 1..];
+
+    (0..=uri.len())
+        .filter(|&len| (optional || len > 0) && uri.is_char_boundary(len))
+        .any(|len| matches_template(rest, &uri[len..]))
+}
+
+/// Cursors handed out by the trigger are opaque offsets into the merged list
+fn encode_cursor(offset: usize) -> String {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(offset.to_string())
+}
+
+fn decode_cursor(cursor: &str) -> Option<usize> {
+    let bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(cursor).ok()?;
+    String::from_utf8(bytes).ok()?.parse().ok()
+}
+
+fn invalid_cursor(cursor: &str) -> mcp::Response {
+    mcp::Response::Error(mcp::Error {
+        code: crate::INVALID_PARAMS,
+        message: format!("Invalid cursor: {cursor}"),
+        data: None,
+    })
+}
+
+fn not_found(kind: &str, name: &str) -> mcp::Response {
+    mcp::Response::Error(mcp::Error {
+        code: crate::INVALID_PARAMS,
+        message: format!("Unknown {kind}: {name}"),
+        data: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(prefixes: &[Option<&str>]) -> McpRoute {
+        McpRoute {
+            path: "/mcp".to_string(),
+            components: prefixes
+                .iter()
+                .enumerate()
+                .map(|(index, prefix)| RouteComponent {
+                    id: format!("component-{index}"),
+                    prefix: prefix.map(str::to_string),
+                })
+                .collect(),
+            list_cache: None,
+            list_page_size: None,
+            server_info: ServerInfo::default(),
+        }
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn merge_keeps_the_first_owner_of_a_name() {
+        let route = route(&[None, None]);
+        let lists = [names(&["echo", "add"]), names(&["add", "sub"])];
+        let (merged, owners) = merge(&route, Kind::Tool, lists.into_iter(), |name| name, false);
+
+        assert_eq!(merged, names(&["echo", "add", "sub"]));
+        assert_eq!(owners["echo"], 0);
+        assert_eq!(owners["add"], 0);
+        assert_eq!(owners["sub"], 1);
+    }
+
+    #[test]
+    fn merge_prefixes_names_when_asked() {
+        let route = route(&[Some("math_"), None]);
+        let lists = [names(&["add"]), names(&["add"])];
+        let (merged, owners) = merge(&route, Kind::Tool, lists.into_iter(), |name| name, true);
+
+        assert_eq!(merged, names(&["math_add", "add"]));
+        assert_eq!(owners["math_add"], 0);
+        assert_eq!(owners["add"], 1);
+    }
+
+    #[test]
+    fn forgotten_kinds_are_listed_again() {
+        let mut owners = RouteOwners::default();
+        owners.record(Kind::Tool, HashMap::from([("echo".to_string(), 0)]));
+        owners.record(Kind::Prompt, HashMap::from([("greet".to_string(), 1)]));
+
+        owners.forget(Kind::Tool);
+
+        assert_eq!(owners.find(Kind::Tool, "echo"), None);
+        assert!(!owners.listed.contains(&Kind::Tool));
+        assert_eq!(owners.find(Kind::Prompt, "greet"), Some(1));
+        assert!(owners.listed.contains(&Kind::Prompt));
+    }
+
+    #[test]
+    fn cursors_round_trip() {
+        for offset in [0, 1, 50, usize::MAX] {
+            assert_eq!(decode_cursor(&encode_cursor(offset)), Some(offset));
+        }
+    }
+
+    #[test]
+    fn foreign_cursors_are_rejected() {
+        assert_eq!(decode_cursor("not a cursor"), None);
+        assert_eq!(decode_cursor(""), None);
+        let not_a_number = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode("page-2");
+        assert_eq!(decode_cursor(&not_a_number), None);
+    }
+
+    #[test]
+    fn template_without_expressions_matches_itself() {
+        assert!(matches_template("file:///readme", "file:///readme"));
+        assert!(!matches_template("file:///readme", "file:///readme.md"));
+    }
+
+    #[test]
+    fn template_expressions_match_non_empty_text() {
+        assert!(matches_template("file:///{path}", "file:///src/lib.rs"));
+        assert!(matches_template("users://{id}/posts/{post}", "users://7/posts/42"));
+        assert!(!matches_template("file:///{path}", "file:///"));
+        assert!(!matches_template("users://{id}/posts/{post}", "users://7/comments/42"));
+        assert!(!matches_template("file:///{path}", "http://example.com/"));
+    }
+
+    #[test]
+    fn template_query_expressions_are_optional() {
+        assert!(matches_template("items://list{?page}", "items://list"));
+        assert!(matches_template("items://list{?page}", "items://list?page=2"));
+        assert!(matches_template("items://list{?page}{&size}", "items://list?page=2&size=10"));
+    }
+
+    #[test]
+    fn template_with_unclosed_expression_matches_nothing() {
+        assert!(!matches_template("file:///{path", "file:///src"));
+    }
+}
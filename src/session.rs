@@ -0,0 +1,494 @@
+//! MCP session management for the Streamable HTTP transport.
+
+use crate::logging::LogLevel;
+use crate::mcp;
+use crate::protocol::ProtocolVersion;
+use crate::sse::MessageSender;
+use futures::channel::oneshot;
+use futures::future::{AbortHandle, AbortRegistration};
+use http::{HeaderMap, StatusCode};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Header carrying the session ID assigned on `initialize`
+pub(crate) const SESSION_ID_HEADER: &str = "mcp-session-id";
+
+/// Header carrying the negotiated protocol version on later requests
+pub(crate) const PROTOCOL_VERSION_HEADER: &str = "mcp-protocol-version";
+
+/// State kept for a client between requests
+pub(crate) struct Session {
+    id: String,
+    route: String,
+    /// Whether the session ends after a period of inactivity
+    expires: bool,
+    state: Mutex<SessionState>,
+}
+
+struct SessionState {
+    protocol_version: ProtocolVersion,
+    client_capabilities: serde_json::Value,
+    last_seen: Instant,
+    /// Server-to-client SSE streams opened with GET
+    streams: Vec<MessageSender>,
+    /// URIs of resources the client subscribed to
+    subscriptions: HashSet<String>,
+    /// Least severe log level the client wants to receive
+    log_level: LogLevel,
+    /// Requests still being handled, by JSON-RPC ID, so they can be cancelled
+    in_flight: HashMap<String, AbortHandle>,
+    /// Requests sent to the client that are waiting for a reply, by ID
+    client_requests: HashMap<String, oneshot::Sender<ClientReply>>,
+    next_client_request_id: u64,
+    /// Result of the client's last `roots/list`, until it says roots changed
+    roots: Option<serde_json::Value>,
+    /// Bumped whenever the client says its roots changed
+    roots_generation: u64,
+}
+
+/// The client's reply to a request the server sent it
+pub(crate) type ClientReply = Result<serde_json::Value, mcp::Error>;
+
+impl Session {
+    /// Create a session for a client initializing on the given route
+    pub(crate) fn new(route: &str) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            route: route.to_string(),
+            expires: true,
+            state: Mutex::new(SessionState {
+                protocol_version: ProtocolVersion::DEFAULT,
+                client_capabilities: serde_json::Value::Null,
+                last_seen: Instant::now(),
+                streams: Vec::new(),
+                subscriptions: HashSet::new(),
+                log_level: LogLevel::default(),
+                in_flight: HashMap::new(),
+                client_requests: HashMap::new(),
+                next_client_request_id: 0,
+                roots: None,
+                roots_generation: 0,
+            }),
+        }
+    }
+
+    /// Create a session that lasts until it is removed, for a transport
+    /// whose single client stays connected for the trigger's lifetime
+    pub(crate) fn without_expiry(route: &str) -> Self {
+        Self {
+            expires: false,
+            ..Self::new(route)
+        }
+    }
+
+    pub(crate) fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The route the session was started on
+    pub(crate) fn route(&self) -> &str {
+        &self.route
+    }
+
+    /// Record the outcome of the `initialize` handshake
+    pub(crate) fn set_initialized(&self, protocol_version: ProtocolVersion, client_capabilities: serde_json::Value) {
+        let mut state = self.state.lock().unwrap();
+        state.protocol_version = protocol_version;
+        state.client_capabilities = client_capabilities;
+    }
+
+    /// The protocol version negotiated during `initialize`
+    pub(crate) fn protocol_version(&self) -> ProtocolVersion {
+        self.state.lock().unwrap().protocol_version
+    }
+
+    /// Whether the client declared a capability during `initialize`
+    pub(crate) fn client_supports(&self, capability: &str) -> bool {
+        self.state.lock().unwrap().client_capabilities.get(capability).is_some()
+    }
+
+    /// Attach a server-to-client SSE stream to this session
+    pub(crate) fn add_stream(&self, stream: MessageSender) {
+        let mut state = self.state.lock().unwrap();
+        // Drop streams whose clients have gone away
+        state.streams.retain(|stream| !stream.is_closed());
+        state.streams.push(stream);
+    }
+
+    /// Send a message on the most recently opened stream, as each message
+    /// must go to only one of them. Returns whether a stream was open.
+    pub(crate) fn send(&self, message: serde_json::Value) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.streams.retain(|stream| !stream.is_closed());
+        state
+            .streams
+            .last()
+            .is_some_and(|stream| stream.unbounded_send(message).is_ok())
+    }
+
+    pub(crate) fn subscribe(&self, uri: &str) {
+        self.state.lock().unwrap().subscriptions.insert(uri.to_string());
+    }
+
+    pub(crate) fn unsubscribe(&self, uri: &str) {
+        self.state.lock().unwrap().subscriptions.remove(uri);
+    }
+
+    pub(crate) fn is_subscribed(&self, uri: &str) -> bool {
+        self.state.lock().unwrap().subscriptions.contains(uri)
+    }
+
+    /// Set the least severe level of log records to send to the client
+    pub(crate) fn set_log_level(&self, level: LogLevel) {
+        self.state.lock().unwrap().log_level = level;
+    }
+
+    pub(crate) fn log_level(&self) -> LogLevel {
+        self.state.lock().unwrap().log_level
+    }
+
+    /// Start tracking a request, returning the registration that aborts
+    /// its handling when the client cancels it, or `None` if another
+    /// request with the same ID is still in flight
+    pub(crate) fn track_request(&self, id: &serde_json::Value) -> Option<AbortRegistration> {
+        let mut state = self.state.lock().unwrap();
+        match state.in_flight.entry(id.to_string()) {
+            Entry::Occupied(_) => None,
+            Entry::Vacant(entry) => {
+                let (handle, registration) = AbortHandle::new_pair();
+                entry.insert(handle);
+                Some(registration)
+            }
+        }
+    }
+
+    /// Stop tracking a request once it has been handled
+    pub(crate) fn finish_request(&self, id: &serde_json::Value) {
+        self.state.lock().unwrap().in_flight.remove(&id.to_string());
+    }
+
+    /// Abort the handling of a request, returning whether it was in flight
+    pub(crate) fn cancel_request(&self, id: &serde_json::Value) -> bool {
+        let handle = self.state.lock().unwrap().in_flight.remove(&id.to_string());
+        handle.map(|handle| handle.abort()).is_some()
+    }
+
+    /// Allocate the ID of a request to the client, returning it with the
+    /// receiver its reply will be delivered to
+    pub(crate) fn start_client_request(&self) -> (serde_json::Value, oneshot::Receiver<ClientReply>) {
+        let mut state = self.state.lock().unwrap();
+        state.next_client_request_id += 1;
+        let id = serde_json::json!(state.next_client_request_id);
+        let (sender, receiver) = oneshot::channel();
+        state.client_requests.insert(id.to_string(), sender);
+        (id, receiver)
+    }
+
+    /// Deliver the client's reply to a request, returning whether the
+    /// request was waiting for one
+    pub(crate) fn complete_client_request(&self, id: &serde_json::Value, reply: ClientReply) -> bool {
+        let sender = self.state.lock().unwrap().client_requests.remove(&id.to_string());
+        sender.is_some_and(|sender| sender.send(reply).is_ok())
+    }
+
+    /// Stop waiting for the reply to a request, e.g. because it couldn't be sent
+    pub(crate) fn abandon_client_request(&self, id: &serde_json::Value) {
+        self.state.lock().unwrap().client_requests.remove(&id.to_string());
+    }
+
+    /// The client's cached roots, or the generation to cache them under
+    /// once they have been listed
+    pub(crate) fn cached_roots(&self) -> Result<serde_json::Value, u64> {
+        let state = self.state.lock().unwrap();
+        state.roots.clone().ok_or(state.roots_generation)
+    }
+
+    /// Cache the client's roots, unless they changed while being listed
+    pub(crate) fn cache_roots(&self, generation: u64, roots: serde_json::Value) {
+        let mut state = self.state.lock().unwrap();
+        if state.roots_generation == generation {
+            state.roots = Some(roots);
+        }
+    }
+
+    pub(crate) fn invalidate_roots(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.roots = None;
+        state.roots_generation += 1;
+    }
+
+    fn close(&self) {
+        let mut state = self.state.lock().unwrap();
+        for (_, request) in state.in_flight.drain() {
+            request.abort();
+        }
+        // Dropping the senders fails requests still waiting for the client
+        state.client_requests.clear();
+        for stream in state.streams.drain(..) {
+            stream.close_channel();
+        }
+    }
+}
+
+/// All live sessions, expired after a period of inactivity
+pub(crate) struct SessionStore {
+    sessions: Mutex<HashMap<String, Arc<Session>>>,
+    idle_timeout: Duration,
+}
+
+impl SessionStore {
+    pub(crate) fn new(idle_timeout: Duration) -> Self {
+        Self {
+            sessions: Mutex::new(HashMap::new()),
+            idle_timeout,
+        }
+    }
+
+    /// Start tracking a session once its `initialize` request succeeded
+    pub(crate) fn insert(&self, session: Arc<Session>) {
+        let mut sessions = self.sessions.lock().unwrap();
+        self.prune(&mut sessions);
+        sessions.insert(session.id.clone(), session);
+    }
+
+    /// Look up a live session for the given route, refreshing its idle timer
+    pub(crate) fn get(&self, id: &str, route: &str) -> Option<Arc<Session>> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions.get(id)?.clone();
+
+        if self.is_expired(&session) {
+            sessions.remove(id);
+            session.close();
+            return None;
+        }
+        if session.route != route {
+            return None;
+        }
+
+        session.state.lock().unwrap().last_seen = Instant::now();
+        Some(session)
+    }
+
+    /// Resolve the session named in a request's headers. Unknown or expired
+    /// sessions are answered with 404 so the client starts a new one.
+    pub(crate) fn lookup(&self, route: &str, headers: &HeaderMap) -> Result<Option<Arc<Session>>, StatusCode> {
+        let Some(id) = headers.get(SESSION_ID_HEADER).and_then(|value| value.to_str().ok()) else {
+            return Ok(None);
+        };
+        let session = self.get(id, route).ok_or(StatusCode::NOT_FOUND)?;
+
+        // Later requests must repeat the negotiated protocol version
+        if let Some(version) = headers.get(PROTOCOL_VERSION_HEADER) {
+            if version.to_str().ok() != Some(session.protocol_version().as_str()) {
+                return Err(StatusCode::BAD_REQUEST);
+            }
+        }
+
+        Ok(Some(session))
+    }
+
+    /// Every live session
+    pub(crate) fn all(&self) -> Vec<Arc<Session>> {
+        let mut sessions = self.sessions.lock().unwrap();
+        self.prune(&mut sessions);
+        sessions.values().cloned().collect()
+    }
+
+    /// End a session, returning whether it existed
+    pub(crate) fn remove(&self, id: &str) -> bool {
+        let session = self.sessions.lock().unwrap().remove(id);
+        match session {
+            Some(session) => {
+                session.close();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Drop expired sessions, closing their streams
+    fn prune(&self, sessions: &mut HashMap<String, Arc<Session>>) {
+        sessions.retain(|_, session| {
+            let expired = self.is_expired(session);
+            if expired {
+                session.close();
+            }
+            !expired
+        });
+    }
+
+    /// Whether a session has been idle for too long. A client listening on
+    /// an open stream is not idle, even if it never sends anything.
+    fn is_expired(&self, session: &Session) -> bool {
+        if !session.expires {
+            return false;
+        }
+        let mut state = session.state.lock().unwrap();
+        if state.streams.iter().any(|stream| !stream.is_closed()) {
+            state.last_seen = Instant::now();
+        }
+        state.last_seen.elapsed() > self.idle_timeout
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sse;
+
+    const IDLE_TIMEOUT: Duration = Duration::from_millis(20);
+
+    fn expiring_store() -> SessionStore {
+        SessionStore::new(IDLE_TIMEOUT)
+    }
+
+    /// Let sessions become idle for longer than the timeout
+    fn wait() {
+        std::thread::sleep(IDLE_TIMEOUT * 3);
+    }
+
+    fn headers(entries: &[(&'static str, &str)]) -> HeaderMap {
+        entries
+            .iter()
+            .map(|(name, value)| (http::HeaderName::from_static(name), value.parse().unwrap()))
+            .collect()
+    }
+
+    fn lookup(
+        sessions: &SessionStore,
+        route: &str,
+        entries: &[(&'static str, &str)],
+    ) -> Result<Option<String>, StatusCode> {
+        let session = sessions.lookup(route, &headers(entries))?;
+        Ok(session.map(|session| session.id().to_string()))
+    }
+
+    #[test]
+    fn sessions_are_only_found_on_their_route() {
+        let sessions = SessionStore::new(Duration::from_secs(60));
+        let session = Arc::new(Session::new("/mcp"));
+        sessions.insert(session.clone());
+
+        assert!(sessions.get(session.id(), "/mcp").is_some());
+        assert!(sessions.get(session.id(), "/other").is_none());
+        assert!(sessions.get("unknown", "/mcp").is_none());
+    }
+
+    #[test]
+    fn removed_sessions_are_closed() {
+        let sessions = SessionStore::new(Duration::from_secs(60));
+        let session = Arc::new(Session::new("/mcp"));
+        let (sender, _receiver) = sse::channel();
+        session.add_stream(sender);
+        sessions.insert(session.clone());
+
+        assert!(sessions.remove(session.id()));
+        assert!(!sessions.remove(session.id()));
+        assert!(sessions.get(session.id(), "/mcp").is_none());
+        assert!(!session.send(serde_json::json!({})));
+    }
+
+    #[test]
+    fn lookup_resolves_the_session_header() {
+        let sessions = SessionStore::new(Duration::from_secs(60));
+        let session = Arc::new(Session::new("/mcp"));
+        session.set_initialized(ProtocolVersion::V2025_06_18, serde_json::json!({}));
+        sessions.insert(session.clone());
+        let id = session.id();
+
+        assert_eq!(lookup(&sessions, "/mcp", &[]), Ok(None));
+        assert_eq!(lookup(&sessions, "/mcp", &[(SESSION_ID_HEADER, id)]), Ok(Some(id.to_string())));
+        let versioned = [(SESSION_ID_HEADER, id), (PROTOCOL_VERSION_HEADER, "2025-06-18")];
+        assert_eq!(lookup(&sessions, "/mcp", &versioned), Ok(Some(id.to_string())));
+    }
+
+    #[test]
+    fn lookup_rejects_unknown_sessions_and_other_versions() {
+        let sessions = SessionStore::new(Duration::from_secs(60));
+        let session = Arc::new(Session::new("/mcp"));
+        session.set_initialized(ProtocolVersion::V2025_06_18, serde_json::json!({}));
+        sessions.insert(session.clone());
+        let id = session.id();
+
+        assert_eq!(lookup(&sessions, "/mcp", &[(SESSION_ID_HEADER, "unknown")]), Err(StatusCode::NOT_FOUND));
+        assert_eq!(lookup(&sessions, "/other", &[(SESSION_ID_HEADER, id)]), Err(StatusCode::NOT_FOUND));
+        let outdated = [(SESSION_ID_HEADER, id), (PROTOCOL_VERSION_HEADER, "2024-11-05")];
+        assert_eq!(lookup(&sessions, "/mcp", &outdated), Err(StatusCode::BAD_REQUEST));
+    }
+
+    #[test]
+    fn requests_in_flight_can_be_cancelled_once() {
+        let session = Session::new("/mcp");
+        let id = serde_json::json!(1);
+        let registration = session.track_request(&id).unwrap();
+        let handled = futures::future::Abortable::new(async {}, registration);
+
+        assert!(session.cancel_request(&id));
+        assert!(!session.cancel_request(&id));
+        assert!(futures::executor::block_on(handled).is_err());
+    }
+
+    #[test]
+    fn finished_requests_are_not_cancelled() {
+        let session = Session::new("/mcp");
+        let id = serde_json::json!("a");
+        let _registration = session.track_request(&id).unwrap();
+
+        session.finish_request(&id);
+
+        assert!(!session.cancel_request(&id));
+        assert!(session.track_request(&id).is_some());
+    }
+
+    #[test]
+    fn duplicate_ids_in_flight_are_rejected() {
+        let session = Session::new("/mcp");
+        let _registration = session.track_request(&serde_json::json!(1)).unwrap();
+
+        assert!(session.track_request(&serde_json::json!(1)).is_none());
+        // String and number IDs are distinct
+        assert!(session.track_request(&serde_json::json!("1")).is_some());
+    }
+
+    #[test]
+    fn idle_sessions_expire() {
+        let sessions = expiring_store();
+        let session = Arc::new(Session::new("/mcp"));
+        sessions.insert(session.clone());
+        wait();
+
+        assert!(sessions.all().is_empty());
+        assert!(sessions.get(session.id(), "/mcp").is_none());
+    }
+
+    #[test]
+    fn sessions_with_an_open_stream_do_not_expire() {
+        let sessions = expiring_store();
+        let session = Arc::new(Session::new("/mcp"));
+        let (sender, receiver) = sse::channel();
+        session.add_stream(sender);
+        sessions.insert(session.clone());
+        wait();
+
+        assert_eq!(sessions.all().len(), 1);
+        assert!(session.send(serde_json::json!({})));
+
+        // Once the client goes away the session is idle again
+        drop(receiver);
+        wait();
+        assert!(sessions.all().is_empty());
+    }
+
+    #[test]
+    fn sessions_without_expiry_outlive_the_idle_timeout() {
+        let sessions = expiring_store();
+        let session = Arc::new(Session::without_expiry("/mcp"));
+        sessions.insert(session.clone());
+        wait();
+
+        assert_eq!(sessions.all().len(), 1);
+        assert!(sessions.get(session.id(), "/mcp").is_some());
+    }
+}
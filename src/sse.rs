@@ -0,0 +1,63 @@
+//! Server-Sent Events support for the MCP Streamable HTTP transport.
+
+use futures::channel::mpsc;
+use futures::StreamExt;
+use http::{header, HeaderMap, Response as HttpResponse, StatusCode};
+use http_body_util::combinators::UnsyncBoxBody;
+use http_body_util::{BodyExt, Empty, Full, StreamBody};
+use hyper::body::{Bytes, Frame};
+use std::convert::Infallible;
+
+/// Response body used by the MCP HTTP transport
+pub(crate) type Body = UnsyncBoxBody<Bytes, Infallible>;
+
+/// Sending half of an SSE stream, each value is one JSON-RPC message
+pub(crate) type MessageSender = mpsc::UnboundedSender<serde_json::Value>;
+
+/// Receiving half of an SSE stream
+pub(crate) type MessageReceiver = mpsc::UnboundedReceiver<serde_json::Value>;
+
+pub(crate) const EVENT_STREAM: &str = "text/event-stream";
+
+/// Create a new channel for JSON-RPC messages bound for an SSE stream
+pub(crate) fn channel() -> (MessageSender, MessageReceiver) {
+    mpsc::unbounded()
+}
+
+/// A body containing the given bytes
+pub(crate) fn full(bytes: impl Into<Bytes>) -> Body {
+    Full::new(bytes.into()).boxed_unsync()
+}
+
+/// An empty body
+pub(crate) fn empty() -> Body {
+    Empty::new().boxed_unsync()
+}
+
+/// Whether the `Accept` header lists `text/event-stream`
+pub(crate) fn accepts_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|media_range| media_range.split(';').next().unwrap_or_default().trim())
+        .any(|media_type| media_type.eq_ignore_ascii_case(EVENT_STREAM))
+}
+
+/// Build a `text/event-stream` response that emits every message sent on the
+/// channel. The stream ends once all senders have been dropped.
+pub(crate) fn response(receiver: MessageReceiver) -> anyhow::Result<HttpResponse<Body>> {
+    let events = receiver.map(|message| Ok(Frame::data(encode_event(&message))));
+
+    Ok(HttpResponse::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, EVENT_STREAM)
+        .header(header::CACHE_CONTROL, "no-cache")
+        .body(StreamBody::new(events).boxed_unsync())?)
+}
+
+fn encode_event(message: &serde_json::Value) -> Bytes {
+    // Serialized JSON never contains raw newlines, so a single data line is enough
+    Bytes::from(format!("event: message\ndata: {message}\n\n"))
+}
@@ -0,0 +1,73 @@
+//! stdio transport: newline-delimited JSON-RPC over stdin and stdout, for
+//! clients that launch the server as a subprocess.
+
+use crate::route::McpRoute;
+use crate::session::Session;
+use crate::{sse, JsonRpcPayload, McpServer};
+use anyhow::Result;
+use futures::StreamExt;
+use spin_factors::RuntimeFactors;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::task;
+use tracing::{info, warn};
+
+impl<F: RuntimeFactors> McpServer<F> {
+    /// Serve a single route over stdio until stdin is closed
+    pub(crate) async fn serve_stdio(self: Arc<Self>, route: Arc<McpRoute>) -> Result<()> {
+        info!("MCP trigger serving route {} over stdio", route.path);
+
+        // All output goes through one writer so messages are never interleaved
+        let (sender, mut receiver) = sse::channel();
+        let writer = task::spawn(async move {
+            let mut stdout = tokio::io::stdout();
+            while let Some(message) = receiver.next().await {
+                let mut line = message.to_string();
+                line.push('\n');
+                stdout.write_all(line.as_bytes()).await?;
+                stdout.flush().await?;
+            }
+            anyhow::Ok(())
+        });
+
+        // A stdio connection is a single client, so it is a single session
+        // that lasts as long as stdin is open. Notifications for it go
+        // straight to stdout.
+        let session = Arc::new(Session::without_expiry(&route.path));
+        session.add_stream(sender.clone());
+        self.sessions.insert(session.clone());
+
+        let mut lines = BufReader::new(tokio::io::stdin()).lines();
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let payload = JsonRpcPayload::parse(line.as_bytes());
+
+            // Handle messages concurrently so a slow tool call doesn't block others
+            let server = self.clone();
+            let route = route.clone();
+            let session = session.clone();
+            let sender = sender.clone();
+            task::spawn(async move {
+                let version = session.protocol_version();
+                match server
+                    .handle_payload(&route, Some(&session), None, version, payload)
+                    .await
+                {
+                    Ok(Some(reply)) => {
+                        let _ = sender.unbounded_send(reply);
+                    }
+                    Ok(None) => {}
+                    Err(err) => warn!("Error handling MCP request: {err:?}"),
+                }
+            });
+        }
+
+        info!("stdin closed, shutting down stdio transport");
+        self.sessions.remove(session.id());
+        drop(sender);
+        writer.await?
+    }
+}
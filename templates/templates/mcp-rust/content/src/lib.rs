@@ -27,20 +27,20 @@ static ALLOC: talc::Talck<talc::locking::AssumeUnlockable, talc::ClaimOnOom> = {
 #[mcp_component]
 fn handle_request(request: Request) -> Response {
     match request {
-        Request::ToolsList => {
-            Response::ToolsList(tools::get_tools_list())
+        Request::ToolsList(_) => {
+            Response::ToolsList(tools::get_tools_list().into())
         }
         
         Request::ToolsCall(params) => {
             Response::ToolsCall(tools::handle_tool_call(&params.name, &params.arguments))
         }
         
-        Request::ResourcesList => {
-            Response::ResourcesList(resources::get_resources_list())
+        Request::ResourcesList(_) => {
+            Response::ResourcesList(resources::get_resources_list().into())
         }
         
-        Request::PromptsList => {
-            Response::PromptsList(prompts::get_prompts_list())
+        Request::PromptsList(_) => {
+            Response::PromptsList(prompts::get_prompts_list().into())
         }
         
         Request::Ping => Response::Pong,
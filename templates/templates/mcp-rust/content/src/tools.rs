@@ -16,6 +16,9 @@ pub fn get_tools_list() -> Vec<Tool> {
                 },
                 "required": ["message"]
             }).to_string(),
+            output_schema: None,
+            title: None,
+            annotations: None,
         }
     ]
 }